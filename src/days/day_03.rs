@@ -33,9 +33,9 @@ pub fn run() {
 }
 
 fn item_priority(item: char) -> u32 {
-	if ('a'..='z').contains(&item) {
+	if item.is_ascii_lowercase() {
 		u32::from(item as u8 - b'a') + 1
-	} else if ('A'..='Z').contains(&item) {
+	} else if item.is_ascii_uppercase() {
 		u32::from(item as u8 - b'A') + 27
 	} else {
 		panic!("Invalid item");
@@ -1,6 +1,9 @@
 //! Day 5.
 
-use std::str::FromStr;
+use std::{
+	fmt::{self, Display},
+	str::FromStr,
+};
 
 use error_stack::{report, IntoReport, Report, ResultExt};
 use lazy_regex::regex_captures;
@@ -10,60 +13,266 @@ fn input() -> &'static str {
 	include_str!("day_05.txt")
 }
 
-pub fn run() {
-	let mut hanoi1: Hanoi = input().parse().expect("parsing");
-	let mut hanoi2 = hanoi1.clone();
+pub fn run(animate: bool) {
+	let hanoi: Hanoi = input().parse().expect("parsing");
+	for diagnostic in hanoi.validate(&CrateMover9000) {
+		eprintln!("{diagnostic}");
+	}
 
 	println!("Part 1:");
-	hanoi1.apply_steps_one().expect("applying steps");
+	if animate {
+		print_frames(&hanoi, &CrateMover9000);
+	}
+	let mut hanoi1 = hanoi.clone();
+	hanoi1.apply_steps(&CrateMover9000).expect("applying steps");
 	let top_crates = hanoi1.top_crates();
 	println!("Top crates: {top_crates}");
 	println!();
 
 	println!("Part 2:");
-	hanoi2.apply_steps_multiple().expect("applying steps");
+	if animate {
+		print_frames(&hanoi, &CrateMover9001);
+	}
+	let mut hanoi2 = hanoi;
+	hanoi2.apply_steps(&CrateMover9001).expect("applying steps");
 	let top_crates = hanoi2.top_crates();
 	println!("Top crates: {top_crates}");
 }
 
+/// Print the towers after each step of the simulation.
+fn print_frames(hanoi: &Hanoi, crane: &dyn Crane) {
+	println!("{}", hanoi.towers);
+	for frame in hanoi.simulate(crane) {
+		let (step, towers) = frame.expect("applying steps");
+		println!("{step}");
+		println!();
+		println!("{towers}");
+	}
+}
+
 /// Towers of hanoi, advent of code edition.
-#[derive(Debug, Clone)]
-struct Hanoi {
-	towers: Vec<Vec<ItemCrate>>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hanoi {
+	towers: Towers,
 	steps: Vec<HanoiStep>,
 }
 
 impl Hanoi {
-	/// Apply the steps to the towers, moving one crate at a time.
-	fn apply_steps_one(&mut self) -> Result<(), Error> {
+	/// Apply the steps to the towers using the given crane.
+	pub fn apply_steps(&mut self, crane: &dyn Crane) -> Result<(), Error> {
 		for step in self.steps.drain(..) {
-			for _ in 0..step.moved {
-				let item = self
-					.towers
-					.get_mut(step.from - 1)
-					.ok_or(Error::MovementIndexOutOfBounds)?
-					.pop()
-					.ok_or(Error::MovementIndexOutOfBounds)?;
-				self.towers.get_mut(step.to - 1).ok_or(Error::MovementIndexOutOfBounds)?.push(item);
+			crane.apply_step(&mut self.towers, step)?;
+		}
+		Ok(())
+	}
+
+	/// Step through the movements using the given crane without modifying this
+	/// state, yielding each step together with the towers after applying it.
+	pub fn simulate<'a>(&'a self, crane: &'a dyn Crane) -> Simulation<'a> {
+		Simulation { towers: self.towers.clone(), steps: self.steps.iter(), crane }
+	}
+
+	/// Dry-run the steps using the given crane and report every step that
+	/// cannot be applied. Failing steps are skipped, so that the following steps
+	/// are checked as well.
+	pub fn validate(&self, crane: &dyn Crane) -> Vec<StepDiagnostic> {
+		let mut towers = self.towers.clone();
+		let mut diagnostics = Vec::new();
+		for (i, step) in self.steps.iter().enumerate() {
+			let mut next = towers.clone();
+			let result = towers.check_step(*step).and_then(|_| crane.apply_step(&mut next, *step));
+			match result {
+				Ok(()) => towers = next,
+				Err(error) => diagnostics.push(StepDiagnostic {
+					step_number: i + 1,
+					step: *step,
+					error,
+					towers: towers.clone(),
+				}),
 			}
 		}
+		diagnostics
+	}
+
+	/// Get the top crate of each tower and combine them to a string.
+	pub fn top_crates(&self) -> String {
+		self.towers.0.iter().filter_map(|tower| tower.last()).map(|item| item.0).collect()
+	}
+}
+
+/// Diagnostic of a step that cannot be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepDiagnostic {
+	/// Number of the step (1-indexed).
+	pub step_number: usize,
+	/// The failing step.
+	pub step: HanoiStep,
+	/// Why the step failed.
+	pub error: Error,
+	/// State of the towers before the step.
+	pub towers: Towers,
+}
+
+impl Display for StepDiagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Step {} `{}`: {}", self.step_number, self.step, self.error)?;
+		write!(f, "{}", self.towers)
+	}
+}
+
+/// Iterator over the states of the towers after each step.
+pub struct Simulation<'a> {
+	/// Current state of the towers.
+	towers: Towers,
+	/// Remaining steps.
+	steps: std::slice::Iter<'a, HanoiStep>,
+	/// Crane used to move the crates.
+	crane: &'a dyn Crane,
+}
+
+impl Iterator for Simulation<'_> {
+	type Item = Result<(HanoiStep, Towers), Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let step = *self.steps.next()?;
+		if let Err(err) = self.crane.apply_step(&mut self.towers, step) {
+			// Stop after the first error.
+			self.steps = [].iter();
+			return Some(Err(err));
+		}
+		Some(Ok((step, self.towers.clone())))
+	}
+}
+
+/// A crane model, moving crates between the towers.
+pub trait Crane {
+	/// Apply a step to the towers.
+	fn apply_step(&self, towers: &mut Towers, step: HanoiStep) -> Result<(), Error>;
+}
+
+/// CrateMover 9000, moving one crate at a time.
+#[derive(Debug, Clone, Copy)]
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+	fn apply_step(&self, towers: &mut Towers, step: HanoiStep) -> Result<(), Error> {
+		for _ in 0..step.moved {
+			towers.lift(step.from, step.to, 1)?;
+		}
 		Ok(())
 	}
+}
 
-	/// Apply the steps to the towers, moving multiple crates at a time.
-	fn apply_steps_multiple(&mut self) -> Result<(), Error> {
-		for step in self.steps.drain(..) {
-			let tower =
-				self.towers.get_mut(step.from - 1).ok_or(Error::MovementIndexOutOfBounds)?;
-			let items = tower.split_off(tower.len() - step.moved as usize);
-			self.towers.get_mut(step.to - 1).ok_or(Error::MovementIndexOutOfBounds)?.extend(items);
+/// CrateMover 9001, moving all crates of a step at once.
+#[derive(Debug, Clone, Copy)]
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+	fn apply_step(&self, towers: &mut Towers, step: HanoiStep) -> Result<(), Error> {
+		towers.lift(step.from, step.to, step.moved.into())
+	}
+}
+
+/// Crane moving at most `capacity` crates per lift, as a block.
+#[derive(Debug, Clone, Copy)]
+pub struct CapacityCrane {
+	/// Maximum number of crates per lift. A crane without capacity fails every
+	/// step with [Error::ZeroCapacity].
+	pub capacity: usize,
+}
+
+impl Crane for CapacityCrane {
+	fn apply_step(&self, towers: &mut Towers, step: HanoiStep) -> Result<(), Error> {
+		if self.capacity == 0 {
+			return Err(Error::ZeroCapacity);
+		}
+
+		let mut remaining = usize::from(step.moved);
+		while remaining > 0 {
+			let lifted = remaining.min(self.capacity);
+			towers.lift(step.from, step.to, lifted)?;
+			remaining -= lifted;
 		}
 		Ok(())
 	}
+}
 
-	/// Get the top crate of each tower and combine them to a string.
-	fn top_crates(&self) -> String {
-		self.towers.iter().filter_map(|tower| tower.last()).map(|item| item.0).collect()
+/// Crane refusing to move crates onto a tower that is taller than the one the
+/// crates are taken from. Moves the crates using the inner crane otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct CarefulCrane<C>(pub C);
+
+impl<C: Crane> Crane for CarefulCrane<C> {
+	fn apply_step(&self, towers: &mut Towers, step: HanoiStep) -> Result<(), Error> {
+		if towers.tower(step.to)?.len() > towers.tower(step.from)?.len() {
+			return Err(Error::MoveOntoTallerTower);
+		}
+		self.0.apply_step(towers, step)
+	}
+}
+
+/// The towers of crates, from bottom to top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Towers(Vec<Vec<ItemCrate>>);
+
+impl Towers {
+	/// Get the tower with the given (1-indexed) number.
+	pub fn tower(&self, number: usize) -> Result<&[ItemCrate], Error> {
+		number
+			.checked_sub(1)
+			.and_then(|index| self.0.get(index))
+			.map(Vec::as_slice)
+			.ok_or(Error::UnknownTower(number))
+	}
+
+	/// Check whether the step can be applied to the towers.
+	pub fn check_step(&self, step: HanoiStep) -> Result<(), Error> {
+		let available = self.tower(step.from)?.len();
+		self.tower(step.to)?;
+		if step.from == step.to {
+			return Err(Error::SameTower(step.from));
+		}
+		if available < step.moved.into() {
+			return Err(Error::NotEnoughCrates {
+				tower: step.from,
+				available,
+				moved: step.moved.into(),
+			});
+		}
+		Ok(())
+	}
+
+	/// Lift the given number of crates from one tower to the other (both
+	/// 1-indexed), keeping their order.
+	pub fn lift(&mut self, from: usize, to: usize, count: usize) -> Result<(), Error> {
+		self.tower(to)?;
+		let available = self.tower(from)?.len();
+		let remaining = available.checked_sub(count).ok_or(Error::NotEnoughCrates {
+			tower: from,
+			available,
+			moved: count,
+		})?;
+		let items = self.0[from - 1].split_off(remaining);
+		self.0[to - 1].extend(items);
+		Ok(())
+	}
+}
+
+impl Display for Towers {
+	/// Draw the towers in the puzzle's format, including the numbers below.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let height = self.0.iter().map(Vec::len).max().unwrap_or(0);
+		for y in (0..height).rev() {
+			let line: Vec<String> = self
+				.0
+				.iter()
+				.map(|tower| tower.get(y).map_or_else(|| "   ".to_owned(), ItemCrate::to_string))
+				.collect();
+			writeln!(f, "{}", line.join(" "))?;
+		}
+
+		let numbers: Vec<String> = (1..=self.0.len()).map(|i| format!("{i:^3}")).collect();
+		writeln!(f, "{}", numbers.join(" "))
 	}
 }
 
@@ -74,23 +283,23 @@ impl FromStr for Hanoi {
 		let (towers, steps) = s.split_once("\n\n").ok_or(ParseError::NoTowerStepsSeparation)?;
 
 		let mut tower_lines: Vec<&str> = towers.lines().collect();
-		let number_of_towers = tower_lines
-			.last()
-			.ok_or(ParseError::WrongTower)?
-			.split_whitespace()
-			.filter(|s| !s.is_empty())
-			.count();
-		tower_lines.pop();
+		let labels = tower_lines.pop().ok_or(ParseError::WrongTower)?;
+		let number_of_towers = labels.split_whitespace().count();
+		let labels_in_order =
+			labels.split_whitespace().zip(1..).all(|(label, i)| label.parse() == Ok(i));
+		if number_of_towers == 0 || !labels_in_order {
+			return Err(report!(ParseError::WrongTower));
+		}
 		tower_lines.reverse();
-		let mut towers = Vec::new();
+		let mut towers = Towers(Vec::new());
 		for x in 0..number_of_towers {
 			let crates = tower_lines
 				.iter()
-				.map(|line| line.split_at(x * 4).1.split_at(3).0)
+				.map(|line| line.chars().skip(x * 4).take(3).collect::<String>())
 				.filter(|item| !item.trim().is_empty())
-				.map(ItemCrate::from_str)
+				.map(|item| ItemCrate::from_str(&item))
 				.collect::<Result<_, _>>()?;
-			towers.push(crates);
+			towers.0.push(crates);
 		}
 
 		let steps = steps.lines().map(HanoiStep::from_str).collect::<Result<_, _>>()?;
@@ -98,9 +307,20 @@ impl FromStr for Hanoi {
 	}
 }
 
+impl Display for Hanoi {
+	/// Write the towers and steps in the puzzle's format.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{}", self.towers)?;
+		for step in &self.steps {
+			writeln!(f, "{step}")?;
+		}
+		Ok(())
+	}
+}
+
 /// A crate, denoted by e.g. `[C]`.
-#[derive(Debug, Clone, Copy)]
-struct ItemCrate(char);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemCrate(char);
 
 impl FromStr for ItemCrate {
 	type Err = Report<ParseError>;
@@ -117,9 +337,15 @@ impl FromStr for ItemCrate {
 	}
 }
 
+impl Display for ItemCrate {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "[{}]", self.0)
+	}
+}
+
 /// A step of Hanoi movement: move of x crates from one tower to the other.
-#[derive(Debug, Clone, Copy)]
-struct HanoiStep {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HanoiStep {
 	/// Number of creates moved.
 	moved: u8,
 	/// From this tower (1-indexed).
@@ -152,9 +378,15 @@ impl FromStr for HanoiStep {
 	}
 }
 
+impl Display for HanoiStep {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "move {} from {} to {}", self.moved, self.from, self.to)
+	}
+}
+
 /// Parsing error.
 #[derive(Debug, Error)]
-enum ParseError {
+pub enum ParseError {
 	#[error("No separation between towers part and steps part found")]
 	NoTowerStepsSeparation,
 	#[error("Wrong Tower format")]
@@ -168,8 +400,140 @@ enum ParseError {
 }
 
 /// Execution error.
-#[derive(Debug, Error)]
-enum Error {
-	#[error("Movement index out of bounds")]
-	MovementIndexOutOfBounds,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Error {
+	#[error("Tower {0} does not exist")]
+	UnknownTower(usize),
+	#[error("Tower {tower} has only {available} crates, but {moved} are moved")]
+	NotEnoughCrates { tower: usize, available: usize, moved: usize },
+	#[error("Crates are moved from tower {0} onto itself")]
+	SameTower(usize),
+	#[error("Crane refused to move crates onto a taller tower")]
+	MoveOntoTallerTower,
+	#[error("Crane cannot lift any crates")]
+	ZeroCapacity,
+}
+
+#[cfg(test)]
+mod tests {
+	use proptest::prelude::*;
+
+	use super::*;
+
+	/// Example input.
+	const EXAMPLE: &str = r#"    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3 
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2"#;
+
+	#[test]
+	fn rendering_frames() {
+		let hanoi: Hanoi = EXAMPLE.parse().expect("parsing");
+		assert_eq!(
+			hanoi.towers.to_string(),
+			EXAMPLE.split_once("\n\n").unwrap().0.to_owned() + "\n"
+		);
+
+		let frames: Vec<(HanoiStep, Towers)> =
+			hanoi.simulate(&CrateMover9000).collect::<Result<_, _>>().expect("applying steps");
+		assert_eq!(frames.len(), 4);
+		assert_eq!(frames[0].0.to_string(), "move 1 from 2 to 1");
+		assert_eq!(frames[0].1.to_string(), "[D]        \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n");
+		assert_eq!(
+			frames[1].1.to_string(),
+			"        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3 \n"
+		);
+	}
+
+	#[test]
+	fn crane_models() {
+		let hanoi: Hanoi = EXAMPLE.parse().expect("parsing");
+		let top_crates = |crane: &dyn Crane| {
+			let mut hanoi = hanoi.clone();
+			hanoi.apply_steps(crane).map(|_| hanoi.top_crates())
+		};
+
+		assert_eq!(top_crates(&CrateMover9000).unwrap(), "CMZ");
+		assert_eq!(top_crates(&CrateMover9001).unwrap(), "MCD");
+		assert_eq!(top_crates(&CapacityCrane { capacity: 1 }).unwrap(), "CMZ");
+		assert_eq!(top_crates(&CapacityCrane { capacity: 3 }).unwrap(), "MCD");
+		assert_eq!(top_crates(&CapacityCrane { capacity: 2 }).unwrap(), "MCZ");
+		assert_eq!(top_crates(&CapacityCrane { capacity: 0 }), Err(Error::ZeroCapacity));
+		assert_eq!(top_crates(&CarefulCrane(CrateMover9000)).unwrap(), "CMZ");
+
+		let mut towers = hanoi.towers.clone();
+		let step = HanoiStep { moved: 1, from: 3, to: 2 };
+		assert!(matches!(
+			CarefulCrane(CrateMover9000).apply_step(&mut towers, step),
+			Err(Error::MoveOntoTallerTower)
+		));
+	}
+
+	#[test]
+	fn printing() {
+		let hanoi: Hanoi = EXAMPLE.parse().expect("parsing");
+		assert_eq!(hanoi.to_string(), format!("{EXAMPLE}\n"));
+	}
+
+	/// Strategy generating arbitrary towers and steps between them.
+	fn hanoi_strategy() -> impl Strategy<Value = Hanoi> {
+		let crates = prop::collection::vec(prop::char::range('A', 'Z').prop_map(ItemCrate), 0..8);
+		prop::collection::vec(crates, 1..15).prop_flat_map(|towers| {
+			let number_of_towers = towers.len();
+			let step = (0..30_u8, 1..=number_of_towers, 1..=number_of_towers)
+				.prop_map(|(moved, from, to)| HanoiStep { moved, from, to });
+			prop::collection::vec(step, 0..10)
+				.prop_map(move |steps| Hanoi { towers: Towers(towers.clone()), steps })
+		})
+	}
+
+	proptest! {
+		#[test]
+		fn roundtrip(hanoi in hanoi_strategy()) {
+			let printed = hanoi.to_string();
+			let parsed: Hanoi = printed.parse().expect("parsing");
+			prop_assert_eq!(&parsed, &hanoi);
+			prop_assert_eq!(parsed.to_string(), printed);
+		}
+	}
+
+	#[test]
+	fn validation() {
+		let mut hanoi: Hanoi = EXAMPLE.parse().expect("parsing");
+		assert!(hanoi.validate(&CrateMover9000).is_empty());
+
+		hanoi.steps.insert(1, HanoiStep { moved: 1, from: 0, to: 1 });
+		hanoi.steps.insert(2, HanoiStep { moved: 1, from: 2, to: 4 });
+		hanoi.steps.insert(3, HanoiStep { moved: 1, from: 3, to: 3 });
+		hanoi.steps.insert(4, HanoiStep { moved: 4, from: 1, to: 3 });
+		let diagnostics = hanoi.validate(&CrateMover9001);
+		let errors: Vec<(usize, Error)> = diagnostics
+			.iter()
+			.map(|diagnostic| (diagnostic.step_number, diagnostic.error))
+			.collect();
+		assert_eq!(
+			errors,
+			[
+				(2, Error::UnknownTower(0)),
+				(3, Error::UnknownTower(4)),
+				(4, Error::SameTower(3)),
+				(5, Error::NotEnoughCrates { tower: 1, available: 3, moved: 4 }),
+			]
+		);
+		assert_eq!(
+			diagnostics[3].towers.to_string(),
+			"[D]        \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n"
+		);
+
+		let mut towers = hanoi.towers.clone();
+		assert_eq!(
+			CrateMover9001.apply_step(&mut towers, HanoiStep { moved: 4, from: 2, to: 1 }),
+			Err(Error::NotEnoughCrates { tower: 2, available: 3, moved: 4 })
+		);
+	}
 }
@@ -1,39 +1,40 @@
 //! Day 7.
 
-use std::collections::HashMap;
+use std::{
+	borrow::Borrow,
+	cell::OnceCell,
+	collections::{btree_map, BTreeMap, HashMap, HashSet},
+	fmt::{self, Display},
+	str::FromStr,
+};
+
+use thiserror::Error;
 
 fn input() -> &'static str {
 	include_str!("day_07.txt")
 }
 
 pub fn run() {
-	let file_system = Directory::from_shell_output(input());
+	let (file_system, warnings) = Directory::from_shell_output(input(), false).expect("parsing");
+	for warning in warnings {
+		eprintln!("Warning: {warning}");
+	}
 
 	println!("Part 1:");
-	let sum_of_sizes: usize = file_system
-		.all_directories()
-		.into_iter()
-		.map(Directory::size)
-		.filter(|size| *size <= 100_000)
-		.sum();
+	let sum_of_sizes: usize =
+		file_system.all_directories().map(Directory::size).filter(|size| *size <= 100_000).sum();
 	println!("Sum of directories below a size of 100k: {sum_of_sizes}");
 	println!();
 
 	println!("Part 2:");
-	let required_to_free = file_system.size() - 40_000_000;
-	let smallest_large_enough = file_system
-		.all_directories()
-		.into_iter()
-		.map(Directory::size)
-		.filter(|size| *size >= required_to_free)
-		.min()
-		.expect("no such folder found");
+	let planner = CleanupPlanner::new(&file_system, 70_000_000, 30_000_000);
+	let (_, smallest_large_enough) = planner.best_directory().expect("no such folder found");
 	println!("Smallest folder size that frees up enough space: {smallest_large_enough}");
 }
 
 /// A filesystem item.
 #[derive(Debug)]
-enum FileSystemItem {
+pub enum FileSystemItem {
 	/// A File.
 	File(File),
 	/// A directory.
@@ -68,7 +69,7 @@ impl FileSystemItem {
 
 /// A file in the filesystem.
 #[derive(Debug, Default)]
-struct File {
+pub struct File {
 	/// File size.
 	size: usize,
 }
@@ -82,86 +83,819 @@ impl File {
 
 /// A directory in the filesystem.
 #[derive(Debug, Default)]
-struct Directory {
-	/// A map from names to the item.
-	items: HashMap<String, FileSystemItem>,
+pub struct Directory {
+	/// A map from names to the item, ordered by name.
+	items: BTreeMap<String, FileSystemItem>,
+	/// Cached total recursive size, computed on first use.
+	size: OnceCell<usize>,
 }
 
 impl Directory {
-	/// Get the directories total recursive size.
+	/// Get the directories total recursive size. It is computed once and
+	/// cached afterwards.
 	pub fn size(&self) -> usize {
-		self.items.values().map(FileSystemItem::size).sum()
+		*self.size.get_or_init(|| self.items.values().map(FileSystemItem::size).sum())
+	}
+
+	/// Look up the item at the given path below this directory, e.g. `/a/e`.
+	pub fn get(&self, path: &str) -> Option<&FileSystemItem> {
+		let mut names = path_segments(path);
+		let first = self.items.get(names.next()?)?;
+		names.try_fold(first, |item, name| item.as_directory()?.items.get(name))
+	}
+
+	/// Look up the directory at the given path, where `/` is this directory
+	/// itself.
+	pub fn get_directory(&self, path: &str) -> Option<&Self> {
+		path_segments(path).try_fold(self, |dir, name| dir.items.get(name)?.as_directory())
 	}
 
-	/// Gets or creates the directory at the specified position.
-	pub fn get_or_create_directory_mut(&mut self, names: &[&str]) -> Option<&mut Self> {
+	/// Iterate over all items below this directory with their full paths,
+	/// depth-first and ordered by name.
+	pub fn walk(&self) -> Walk<'_> {
+		Walk { stack: vec![(String::new(), self.items.iter())] }
+	}
+
+	/// Return the sizes of this and all sub-directories, largest first.
+	pub fn du(&self) -> DiskUsage {
+		let mut entries: Vec<(String, usize)> = std::iter::once(("/".to_owned(), self.size()))
+			.chain(
+				self.walk()
+					.filter_map(|(path, item)| item.as_directory().map(|dir| (path, dir.size()))),
+			)
+			.collect();
+		entries.sort_by(|(path_a, size_a), (path_b, size_b)| {
+			size_b.cmp(size_a).then_with(|| path_a.cmp(path_b))
+		});
+		DiskUsage { entries }
+	}
+
+	/// Write the directory contents as tree in the puzzle's format, indented by
+	/// the given depth.
+	fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+		for (name, item) in &self.items {
+			let indent = "  ".repeat(depth);
+			match item {
+				FileSystemItem::File(file) => {
+					writeln!(f, "{indent}- {name} (file, size={})", file.size())?;
+				}
+				FileSystemItem::Directory(dir) => {
+					writeln!(f, "{indent}- {name} (dir)")?;
+					dir.write_tree(f, depth + 1)?;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Gets or creates the directory at the specified position. Invalidates the
+	/// cached sizes along the way, as the directory might get modified.
+	pub fn get_or_create_directory_mut(&mut self, names: &[String]) -> Option<&mut Self> {
 		let mut current = self;
+		current.size.take();
 		for name in names {
 			current = current
 				.items
-				.entry(name.to_string())
+				.entry(name.clone())
 				.or_insert_with(|| FileSystemItem::Directory(Directory::default()))
 				.as_directory_mut()?;
+			current.size.take();
 		}
 		Some(current)
 	}
 
-	/// Build up a directory from AoC command output.
-	pub fn from_shell_output(shell: &str) -> Self {
-		let mut lines = shell.lines().peekable();
-		let mut root = Self::default();
+	/// Build up a directory from AoC command output. Returns the directory and
+	/// the warnings that were encountered. In strict mode, changing into a
+	/// directory that was not listed before is an error instead of a warning.
+	pub fn from_shell_output(
+		shell: &str,
+		strict: bool,
+	) -> Result<(Self, Vec<ParseError>), ParseError> {
+		let mut lines = shell.lines().enumerate().map(|(i, line)| (i + 1, line)).peekable();
+		let mut tree = TreeBuilder::new();
+		let mut warnings = Vec::new();
 
-		let mut current_folder = Vec::new();
-		while let Some(command) = lines.next() {
-			if command.starts_with("$ cd") {
-				let argument = &command[5..];
-				match argument {
-					"/" => current_folder.clear(),
-					".." => {
-						current_folder.pop();
-					}
-					name => {
-						current_folder.push(name);
+		let mut current = TreeBuilder::ROOT;
+		while let Some((line, command)) = lines.next() {
+			let command =
+				command.strip_prefix("$ ").ok_or(ParseError::OutputWithoutCommand { line })?;
+			match command.split_once(' ') {
+				Some(("cd", path)) => {
+					if let Err(err) = tree.change_directory(&mut current, path, line) {
+						match err {
+							ParseError::UnknownDirectory { .. } if !strict => warnings.push(err),
+							err => return Err(err),
+						}
 					}
 				}
-			} else if command.starts_with("$ ls") {
-				let current = root
-					.get_or_create_directory_mut(&current_folder)
-					.expect("Directories must not be files");
-				while lines.peek().map_or(false, |s| !s.starts_with('$')) {
-					let (left, right) = lines
-						.next()
-						.unwrap()
-						.split_once(' ')
-						.expect("List info must include space");
-					if left == "dir" {
-						current
-							.items
-							.entry(right.to_owned())
-							.or_insert_with(|| FileSystemItem::Directory(Directory::default()));
-					} else {
-						let size = left.parse().expect("number parsing");
-						current.items.insert(right.to_owned(), FileSystemItem::File(File { size }));
+				None if command == "ls" => {
+					tree.make_known(current, line)?;
+					let already_listed = std::mem::replace(&mut tree.nodes[current].listed, true);
+
+					let mut names = HashSet::new();
+					while let Some((line, entry)) = lines.next_if(|(_, s)| !s.starts_with('$')) {
+						let (name, item) = parse_listing_entry(entry, line)?;
+						let conflict = || ParseError::ConflictingListing {
+							line,
+							path: tree.path(current),
+							name: name.to_owned(),
+						};
+						match (tree.nodes[current].items.get(name), item) {
+							(Some(TreeItem::Directory(_)), FileSystemItem::Directory(_)) => {}
+							(Some(TreeItem::File(a)), FileSystemItem::File(b))
+								if a.size == b.size => {}
+							(Some(_), _) => return Err(conflict()),
+							(None, _) if already_listed => return Err(conflict()),
+							(None, FileSystemItem::File(file)) => {
+								tree.nodes[current]
+									.items
+									.insert(name.to_owned(), TreeItem::File(file));
+							}
+							(None, FileSystemItem::Directory(_)) => {
+								let child = tree.child_directory(current, name);
+								tree.nodes[child].known = true;
+								tree.nodes[current].placeholders.remove(name);
+								tree.nodes[current]
+									.items
+									.insert(name.to_owned(), TreeItem::Directory(child));
+							}
+						}
+						names.insert(name);
+					}
+
+					let items = &tree.nodes[current].items;
+					if names.len() != items.len() {
+						// Some previously known entries are missing in this listing.
+						let name = items
+							.keys()
+							.find(|name| !names.contains(name.as_str()))
+							.cloned()
+							.unwrap_or_default();
+						return Err(ParseError::ConflictingListing {
+							line,
+							path: tree.path(current),
+							name,
+						});
 					}
 				}
-			} else {
-				panic!("Unrecognized command: {command}");
+				_ => return Err(ParseError::UnknownCommand { line, command: command.to_owned() }),
 			}
 		}
 
-		root
+		Ok((tree.build(), warnings))
 	}
 
-	/// Return a list of all directories.
-	pub fn all_directories(&self) -> Vec<&Self> {
-		let mut directories = vec![self];
+	/// Iterate over this and all sub-directories.
+	pub fn all_directories(&self) -> impl Iterator<Item = &Self> {
+		let mut stack = vec![self];
+		std::iter::from_fn(move || {
+			let dir = stack.pop()?;
+			stack.extend(dir.items.values().filter_map(FileSystemItem::as_directory));
+			Some(dir)
+		})
+	}
+}
+
+impl Drop for Directory {
+	fn drop(&mut self) {
+		// Drop nested directories iteratively, deep trees would overflow the
+		// stack otherwise.
+		let mut pending = vec![std::mem::take(&mut self.items)];
+		while let Some(items) = pending.pop() {
+			for item in items.into_values() {
+				if let FileSystemItem::Directory(mut dir) = item {
+					pending.push(std::mem::take(&mut dir.items));
+				}
+			}
+		}
+	}
+}
+
+impl Display for Directory {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "- / (dir)")?;
+		self.write_tree(f, 1)
+	}
+}
+
+impl FromStr for Directory {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::from_shell_output(s, true).map(|(root, _)| root)
+	}
+}
+
+/// Directory tree under construction while parsing a shell transcript.
+/// Directories refer to each other by index, so that changing directories
+/// takes constant time regardless of the depth of the tree.
+#[derive(Debug)]
+struct TreeBuilder {
+	/// All directories, where children always come after their parent.
+	nodes: Vec<TreeNode>,
+}
+
+/// A directory under construction.
+#[derive(Debug, Default)]
+struct TreeNode {
+	/// Name of the directory in its parent.
+	name: String,
+	/// Index of the parent directory, `None` for the root.
+	parent: Option<usize>,
+	/// Known items of the directory.
+	items: BTreeMap<String, TreeItem>,
+	/// Directories that were changed into without being known, by name.
+	placeholders: BTreeMap<String, usize>,
+	/// Whether the directory has been listed.
+	listed: bool,
+	/// Whether the directory is part of the tree. Placeholders become known
+	/// when they are listed or something is listed inside of them.
+	known: bool,
+}
+
+/// An item of a directory under construction.
+#[derive(Debug)]
+enum TreeItem {
+	/// A file.
+	File(File),
+	/// A directory, by index.
+	Directory(usize),
+}
 
-		for item in self.items.values() {
+impl TreeBuilder {
+	/// Index of the root directory.
+	const ROOT: usize = 0;
+
+	/// Create a tree consisting of the root directory.
+	fn new() -> Self {
+		Self { nodes: vec![TreeNode { known: true, ..Default::default() }] }
+	}
+
+	/// Format the full path of the directory.
+	fn path(&self, mut index: usize) -> String {
+		let mut names = Vec::new();
+		while let Some(parent) = self.nodes[index].parent {
+			names.push(self.nodes[index].name.clone());
+			index = parent;
+		}
+		names.reverse();
+		format_path(&names)
+	}
+
+	/// Get the placeholder with the given name in the directory, creating it if
+	/// necessary.
+	fn child_directory(&mut self, parent: usize, name: &str) -> usize {
+		if let Some(&child) = self.nodes[parent].placeholders.get(name) {
+			return child;
+		}
+		let child = self.nodes.len();
+		self.nodes.push(TreeNode {
+			name: name.to_owned(),
+			parent: Some(parent),
+			..Default::default()
+		});
+		self.nodes[parent].placeholders.insert(name.to_owned(), child);
+		child
+	}
+
+	/// Make the directory and all its parents part of the tree, failing if one
+	/// of them collides with a file.
+	fn make_known(&mut self, mut index: usize, line: usize) -> Result<(), ParseError> {
+		while !self.nodes[index].known {
+			let parent = self.nodes[index].parent.expect("the root is always known");
+			let name = self.nodes[index].name.clone();
+			if self.nodes[parent].items.contains_key(&name) {
+				return Err(ParseError::NotADirectory { line, path: self.path(index) });
+			}
+			self.nodes[parent].placeholders.remove(&name);
+			self.nodes[parent].items.insert(name, TreeItem::Directory(index));
+			self.nodes[index].known = true;
+			index = parent;
+		}
+		Ok(())
+	}
+
+	/// Apply a `cd` to the given path, which can be relative or absolute and
+	/// consist of multiple segments. Entering a directory that is not known is
+	/// reported as [ParseError::UnknownDirectory], but the current directory is
+	/// changed nonetheless.
+	fn change_directory(
+		&mut self,
+		current: &mut usize,
+		path: &str,
+		line: usize,
+	) -> Result<(), ParseError> {
+		if path.starts_with('/') {
+			*current = Self::ROOT;
+		}
+
+		let mut unknown = false;
+		for name in path_segments(path) {
+			match name {
+				"." => {}
+				".." => *current = self.nodes[*current].parent.unwrap_or(Self::ROOT),
+				name => match self.nodes[*current].items.get(name) {
+					Some(TreeItem::Directory(child)) => *current = *child,
+					Some(TreeItem::File(_)) => {
+						let path = format!("{}/{name}", self.path(*current).trim_end_matches('/'));
+						return Err(ParseError::NotADirectory { line, path });
+					}
+					None => {
+						*current = self.child_directory(*current, name);
+						unknown = true;
+					}
+				},
+			}
+		}
+
+		if unknown {
+			Err(ParseError::UnknownDirectory { line, path: self.path(*current) })
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Build the directory tree bottom-up, dropping placeholders and caching
+	/// the directory sizes on the way.
+	fn build(self) -> Directory {
+		let mut built: Vec<Option<Directory>> = Vec::with_capacity(self.nodes.len());
+		built.resize_with(self.nodes.len(), || None);
+		for (index, node) in self.nodes.into_iter().enumerate().rev() {
+			let items: BTreeMap<String, FileSystemItem> = node
+				.items
+				.into_iter()
+				.map(|(name, item)| {
+					let item = match item {
+						TreeItem::File(file) => FileSystemItem::File(file),
+						TreeItem::Directory(child) => FileSystemItem::Directory(
+							built[child].take().expect("children are built before their parent"),
+						),
+					};
+					(name, item)
+				})
+				.collect();
+			let size: usize = items.values().map(FileSystemItem::size).sum();
+			built[index] = Some(Directory { items, size: OnceCell::from(size) });
+		}
+		built.swap_remove(Self::ROOT).expect("the root is always built")
+	}
+}
+
+/// Parse one line of `ls` output into the item name and the item.
+fn parse_listing_entry(entry: &str, line: usize) -> Result<(&str, FileSystemItem), ParseError> {
+	let (left, name) = entry
+		.split_once(' ')
+		.ok_or_else(|| ParseError::InvalidEntry { line, entry: entry.to_owned() })?;
+	if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+		return Err(ParseError::InvalidEntry { line, entry: entry.to_owned() });
+	}
+
+	if left == "dir" {
+		Ok((name, FileSystemItem::Directory(Directory::default())))
+	} else {
+		let size =
+			left.parse().map_err(|_| ParseError::InvalidSize { line, size: left.to_owned() })?;
+		Ok((name, FileSystemItem::File(File { size })))
+	}
+}
+
+/// Format the path of the given folder names.
+fn format_path<S: Borrow<str>>(names: &[S]) -> String {
+	format!("/{}", names.join("/"))
+}
+
+/// Split a path into the names of its components.
+fn path_segments(path: &str) -> impl Iterator<Item = &str> {
+	path.split('/').filter(|name| !name.is_empty())
+}
+
+/// Depth-first iterator over the items of a directory with their full paths.
+#[derive(Debug)]
+pub struct Walk<'a> {
+	/// Path prefix and remaining items of each directory currently visited.
+	stack: Vec<(String, btree_map::Iter<'a, String, FileSystemItem>)>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+	type Item = (String, &'a FileSystemItem);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let (prefix, items) = self.stack.last_mut()?;
+			let Some((name, item)) = items.next() else {
+				self.stack.pop();
+				continue;
+			};
+
+			let path = format!("{prefix}/{name}");
 			if let Some(dir) = item.as_directory() {
-				directories.extend(dir.all_directories());
+				self.stack.push((path.clone(), dir.items.iter()));
+			}
+			return Some((path, item));
+		}
+	}
+}
+
+/// `du`-style listing of directory sizes.
+#[derive(Debug)]
+pub struct DiskUsage {
+	/// Directory paths and their sizes, largest first.
+	pub entries: Vec<(String, usize)>,
+}
+
+impl Display for DiskUsage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (path, size) in &self.entries {
+			writeln!(f, "{size}\t{path}")?;
+		}
+		Ok(())
+	}
+}
+
+/// Planner for freeing up disk space by deleting directories.
+#[derive(Debug)]
+pub struct CleanupPlanner<'a> {
+	/// Root directory of the filesystem.
+	root: &'a Directory,
+	/// Total disk size.
+	disk_size: usize,
+	/// Free space that is required.
+	required: usize,
+}
+
+impl<'a> CleanupPlanner<'a> {
+	/// Create a new [CleanupPlanner] for the filesystem on a disk of the given
+	/// size, which needs the given amount of free space.
+	pub fn new(root: &'a Directory, disk_size: usize, required: usize) -> Self {
+		Self { root, disk_size, required }
+	}
+
+	/// Amount of space that needs to be freed.
+	pub fn to_free(&self) -> usize {
+		(self.root.size() + self.required).saturating_sub(self.disk_size)
+	}
+
+	/// Return up to `k` directories that each free up enough space on their
+	/// own, smallest first.
+	pub fn candidates(&self, k: usize) -> Vec<(String, usize)> {
+		let to_free = self.to_free();
+		let nodes = DirectoryNode::collect(self.root);
+		let mut candidates: Vec<usize> =
+			(0..nodes.len()).filter(|&i| nodes[i].size >= to_free).collect();
+		candidates.sort_by_key(|&i| nodes[i].size);
+		// Only format the paths needed to break ties, deep trees have long paths.
+		let Some(&last) = candidates.get(k.wrapping_sub(1)).or(candidates.last()) else {
+			return Vec::new();
+		};
+		let limit = nodes[last].size;
+		let mut candidates: Vec<(String, usize)> = candidates
+			.into_iter()
+			.take_while(|&i| nodes[i].size <= limit)
+			.map(|i| (DirectoryNode::path(&nodes, i), nodes[i].size))
+			.collect();
+		candidates.sort_by(|(path_a, size_a), (path_b, size_b)| {
+			size_a.cmp(size_b).then_with(|| path_a.cmp(path_b))
+		});
+		candidates.truncate(k);
+		candidates
+	}
+
+	/// Return the smallest directory that frees up enough space on its own.
+	pub fn best_directory(&self) -> Option<(String, usize)> {
+		self.candidates(1).pop()
+	}
+
+	/// Return the set of non-nested directories that frees up enough space
+	/// while deleting as little as possible. This is an exact knapsack-style
+	/// dynamic programming over the directories in pre-order, where deleting a
+	/// directory skips all of its sub-directories.
+	pub fn best_set(&self) -> Option<Vec<(String, usize)>> {
+		let to_free = self.to_free();
+		if to_free == 0 {
+			return Some(Vec::new());
+		}
+
+		let nodes = DirectoryNode::collect(self.root);
+		if nodes[0].size < to_free {
+			return None;
+		}
+
+		// The amounts below `to_free` that can be freed using the directories
+		// from index `i` on include everything that can be freed from `i + 1` on.
+		// So going backwards, the index each amount is first reached at describes
+		// all of these sets. The sets themselves are only kept while they are
+		// needed for deleting a directory in front of them.
+		let mut uses = vec![0; nodes.len() + 1];
+		for node in &nodes {
+			uses[node.subtree_end] += 1;
+		}
+		let mut saved: HashMap<usize, AmountSet> = HashMap::new();
+		let mut freeable = AmountSet::new(to_free);
+		let mut first_index = vec![0; to_free];
+		let mut best = (nodes[0].size, 0);
+		for (i, node) in nodes.iter().enumerate().rev() {
+			if uses[i + 1] > 0 {
+				saved.insert(i + 1, freeable.clone());
+			}
+			let after = &saved[&node.subtree_end];
+			if let Some(freed) = after.first_from(to_free.saturating_sub(node.size)) {
+				best = best.min((freed + node.size, i));
+			}
+			freeable.add_shifted(after, node.size, |freed| first_index[freed] = i);
+
+			uses[node.subtree_end] -= 1;
+			if uses[node.subtree_end] == 0 {
+				saved.remove(&node.subtree_end);
+			}
+			if best.0 == to_free {
+				break;
+			}
+		}
+
+		// Retrace which directories make up the best amount. Each amount was
+		// first reached by deleting the directory at its index, which leaves an
+		// amount that can be freed after that directory's sub-tree.
+		let mut selected = Vec::new();
+		let (mut freed, mut i) = best;
+		loop {
+			selected.push((DirectoryNode::path(&nodes, i), nodes[i].size));
+			freed -= nodes[i].size;
+			if freed == 0 {
+				break;
+			}
+			i = first_index[freed];
+		}
+		Some(selected)
+	}
+}
+
+/// A directory of the filesystem as seen by the [CleanupPlanner].
+#[derive(Debug)]
+struct DirectoryNode<'a> {
+	/// Name of the directory in its parent.
+	name: &'a str,
+	/// Index of the parent directory, `None` for the root.
+	parent: Option<usize>,
+	/// Total recursive size.
+	size: usize,
+	/// Index after the last sub-directory.
+	subtree_end: usize,
+}
+
+impl<'a> DirectoryNode<'a> {
+	/// Collect this and all sub-directories in pre-order, ordered by name, so
+	/// that all sub-directories of a directory directly follow it.
+	fn collect(root: &'a Directory) -> Vec<Self> {
+		let mut nodes = Vec::new();
+		let mut stack = vec![("", None, root)];
+		while let Some((name, parent, dir)) = stack.pop() {
+			let index = nodes.len();
+			nodes.push(Self { name, parent, size: dir.size(), subtree_end: index + 1 });
+			stack.extend(dir.items.iter().rev().filter_map(|(name, item)| {
+				Some((name.as_str(), Some(index), item.as_directory()?))
+			}));
+		}
+		for i in (0..nodes.len()).rev() {
+			if let Some(parent) = nodes[i].parent {
+				nodes[parent].subtree_end = nodes[parent].subtree_end.max(nodes[i].subtree_end);
 			}
 		}
+		nodes
+	}
+
+	/// Format the full path of the directory at the given index.
+	fn path(nodes: &[Self], mut index: usize) -> String {
+		let mut names = Vec::new();
+		while let Some(parent) = nodes[index].parent {
+			names.push(nodes[index].name);
+			index = parent;
+		}
+		names.reverse();
+		format_path(&names)
+	}
+}
+
+/// Set of amounts below a limit, stored as bitset.
+#[derive(Debug, Clone)]
+struct AmountSet {
+	/// Bits of the amounts, 64 per word.
+	words: Vec<u64>,
+	/// Exclusive upper bound of the amounts.
+	limit: usize,
+}
+
+impl AmountSet {
+	/// Create a set containing only zero, for a limit above zero.
+	fn new(limit: usize) -> Self {
+		let mut words = vec![0; limit.div_ceil(64)];
+		words[0] = 1;
+		Self { words, limit }
+	}
+
+	/// Return the smallest amount in the set that is at least `min`.
+	fn first_from(&self, min: usize) -> Option<usize> {
+		let mut index = min / 64;
+		let mut word = self.words.get(index)? & (u64::MAX << (min % 64));
+		while word == 0 {
+			index += 1;
+			word = *self.words.get(index)?;
+		}
+		Some(index * 64 + word.trailing_zeros() as usize)
+	}
+
+	/// Add all amounts of `other` increased by `offset`, as far as they stay
+	/// below the limit. Calls `added` for every amount that is new to the set.
+	fn add_shifted(&mut self, other: &Self, offset: usize, mut added: impl FnMut(usize)) {
+		let (skip, shift) = (offset / 64, offset % 64);
+		let last = self.words.len() - 1;
+		let last_bits = self.limit - last * 64;
+		for index in skip..self.words.len() {
+			let mut word = other.words[index - skip] << shift;
+			if shift > 0 && index > skip {
+				word |= other.words[index - skip - 1] >> (64 - shift);
+			}
+			if index == last && last_bits < 64 {
+				word &= (1 << last_bits) - 1;
+			}
+
+			let mut new = word & !self.words[index];
+			self.words[index] |= new;
+			while new != 0 {
+				added(index * 64 + new.trailing_zeros() as usize);
+				new &= new - 1;
+			}
+		}
+	}
+}
+
+/// Error while parsing a shell transcript. Every variant carries the 1-based
+/// line number it occurred at.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseError {
+	#[error("Line {line}: `{command}` is not a known command")]
+	UnknownCommand { line: usize, command: String },
+	#[error("Line {line}: command output without preceding command")]
+	OutputWithoutCommand { line: usize },
+	#[error("Line {line}: `{entry}` is not a valid listing entry")]
+	InvalidEntry { line: usize, entry: String },
+	#[error("Line {line}: `{size}` is not a valid file size")]
+	InvalidSize { line: usize, size: String },
+	#[error("Line {line}: `{path}` is a file, not a directory")]
+	NotADirectory { line: usize, path: String },
+	#[error("Line {line}: changed into unknown directory `{path}`")]
+	UnknownDirectory { line: usize, path: String },
+	#[error("Line {line}: listing of `{path}` conflicts with previous listing at `{name}`")]
+	ConflictingListing { line: usize, path: String, name: String },
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Example input.
+	const EXAMPLE: &str = r#"$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k"#;
+
+	#[test]
+	fn paths() {
+		let root: Directory = EXAMPLE.parse().expect("parsing");
+		assert_eq!(root.get("/a/e/i").map(FileSystemItem::size), Some(584));
+		assert_eq!(root.get_directory("/a/e").map(Directory::size), Some(584));
+		assert_eq!(root.get_directory("/").map(Directory::size), Some(48381165));
+		assert!(root.get("/a/f/x").is_none());
+		assert!(root.get_directory("/b.txt").is_none());
+
+		let paths: Vec<String> = root.walk().map(|(path, _)| path).collect();
+		assert_eq!(paths[..4], ["/a", "/a/e", "/a/e/i", "/a/f"]);
+		assert_eq!(paths.len(), 13);
+	}
+
+	#[test]
+	fn printing() {
+		let root: Directory = EXAMPLE.parse().expect("parsing");
+		assert_eq!(
+			root.to_string(),
+			r#"- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"#
+		);
+		assert_eq!(root.du().to_string(), "48381165\t/\n24933642\t/d\n94853\t/a\n584\t/a/e\n");
+	}
+
+	#[test]
+	fn parsing_errors() {
+		let err = "$ cd /\n$ ls\nabc x".parse::<Directory>().unwrap_err();
+		assert_eq!(err, ParseError::InvalidSize { line: 3, size: "abc".to_owned() });
+
+		let err = "$ cd /\n$ rm -rf".parse::<Directory>().unwrap_err();
+		assert_eq!(err, ParseError::UnknownCommand { line: 2, command: "rm -rf".to_owned() });
+
+		let err = "$ ls\ndir a\n$ ls\ndir a\n1 b".parse::<Directory>().unwrap_err();
+		assert_eq!(
+			err,
+			ParseError::ConflictingListing { line: 5, path: "/".to_owned(), name: "b".to_owned() }
+		);
+
+		let err = "$ ls\n1 a\n$ cd a".parse::<Directory>().unwrap_err();
+		assert_eq!(err, ParseError::NotADirectory { line: 3, path: "/a".to_owned() });
+	}
+
+	#[test]
+	fn unknown_directories() {
+		let shell = "$ cd /\n$ ls\ndir a\n$ cd a/b\n$ ls\n5 c\n$ cd /a/../a/b";
+		let err = shell.parse::<Directory>().unwrap_err();
+		assert_eq!(err, ParseError::UnknownDirectory { line: 4, path: "/a/b".to_owned() });
+
+		let (root, warnings) = Directory::from_shell_output(shell, false).expect("parsing");
+		assert_eq!(warnings, [ParseError::UnknownDirectory { line: 4, path: "/a/b".to_owned() }]);
+		assert_eq!(root.get("/a/b/c").map(FileSystemItem::size), Some(5));
+	}
+
+	#[test]
+	fn deep_trees() {
+		let depth = 50_000;
+		let shell = "$ ls\ndir d\n1 f\n$ cd d\n".repeat(depth);
+		let root: Directory = shell.parse().expect("parsing");
+		assert_eq!(root.size(), depth);
+		assert_eq!(root.all_directories().count(), depth + 1);
+	}
+
+	#[test]
+	fn cleanup_planning() {
+		let root: Directory = EXAMPLE.parse().expect("parsing");
+		let planner = CleanupPlanner::new(&root, 70_000_000, 30_000_000);
+		assert_eq!(planner.to_free(), 8381165);
+		assert_eq!(planner.best_directory(), Some(("/d".to_owned(), 24933642)));
+		assert_eq!(planner.candidates(5).len(), 2);
+
+		let planner = CleanupPlanner::new(&root, 48_400_000, 100_000);
+		assert_eq!(planner.to_free(), 81165);
+		assert_eq!(planner.best_directory(), Some(("/a".to_owned(), 94853)));
+		assert_eq!(planner.best_set(), Some(vec![("/a".to_owned(), 94853)]));
+
+		let planner = CleanupPlanner::new(&root, 23_447_465, 0);
+		assert_eq!(planner.to_free(), 24933700);
+		assert_eq!(planner.best_directory(), Some(("/".to_owned(), 48381165)));
+		assert_eq!(
+			planner.best_set(),
+			Some(vec![("/a/e".to_owned(), 584), ("/d".to_owned(), 24933642)])
+		);
+
+		let planner = CleanupPlanner::new(&root, 1_000_000, 2_000_000);
+		assert_eq!(planner.best_directory(), None);
+		assert_eq!(planner.best_set(), None);
+	}
+	#[test]
+	fn cleanup_planning_many_directories() {
+		// 40 directories of even sizes, so there is no set freeing up exactly
+		// the odd amount that is required.
+		let mut shell = String::from("$ cd /\n$ ls\n");
+		for i in 0..40 {
+			shell.push_str(&format!("dir d{i:02}\n"));
+		}
+		for i in 0..40 {
+			shell.push_str(&format!("$ cd d{i:02}\n$ ls\n{} f\n$ cd ..\n", 1000 + 2 * i));
+		}
+		let root: Directory = shell.parse().expect("parsing");
+		assert_eq!(root.size(), 41_560);
 
-		directories
+		let planner = CleanupPlanner::new(&root, 21_559, 0);
+		assert_eq!(planner.to_free(), 20_001);
+		let set = planner.best_set().expect("solution");
+		assert_eq!(set.iter().map(|(_, size)| size).sum::<usize>(), 20_002);
+		assert!(set.iter().all(|(path, _)| path.starts_with("/d")));
 	}
 }
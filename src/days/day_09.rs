@@ -1,27 +1,59 @@
 //! Day 9.
 
-use std::{collections::HashSet, str::FromStr};
+use std::{
+	collections::HashSet,
+	fmt::{self, Display},
+	str::FromStr,
+};
 
 fn input() -> &'static str {
 	include_str!("day_09.txt")
 }
 
-pub fn run() {
+pub fn run(knots: Option<usize>, animate: bool) {
 	let movements: Vec<Movement> =
 		input().lines().map(Movement::from_str).collect::<Result<_, _>>().expect("parsing");
 
 	println!("Part 1:");
-	let mut state = State::<2>::new();
-	let positions = state.run_movements(&movements);
-	let unique_positions: HashSet<Position> = positions.into_iter().collect();
-	println!("Unique tail positions: {}", unique_positions.len());
+	let state = simulate(&movements, 2, animate);
+	println!("Unique tail positions: {}", state.visits().tail().len());
 	println!();
 
 	println!("Part 2:");
-	let mut state = State::<10>::new();
-	let positions = state.run_movements(&movements);
-	let unique_positions: HashSet<Position> = positions.into_iter().collect();
-	println!("Unique tail positions: {}", unique_positions.len());
+	let state = simulate(&movements, 10, animate);
+	println!("Unique tail positions: {}", state.visits().tail().len());
+
+	if let Some(knots) = knots {
+		println!();
+		println!("Rope with {knots} knots:");
+		let state = simulate(&movements, knots, animate);
+		let visits = state.visits();
+		for (i, (visited, distance)) in visits.visited.iter().zip(&visits.distances).enumerate() {
+			println!("Knot {i}: {} unique positions, traveled {distance} steps", visited.len());
+		}
+	}
+}
+
+/// Simulate a rope with the given number of knots and return the final state.
+/// Prints the rope after each movement and the tail's visited positions at the
+/// end if `animate` is set.
+fn simulate(movements: &[Movement], knots: usize, animate: bool) -> State {
+	let mut state = State::new(knots);
+	if animate {
+		println!("== Initial State ==");
+		println!();
+		println!("{}", state.render());
+		for movement in movements {
+			state.run_movements(std::slice::from_ref(movement));
+			println!("== {movement} ==");
+			println!();
+			println!("{}", state.render());
+		}
+		println!("{}", render_visited(state.visits().tail()));
+	} else {
+		state.run_movements(movements);
+	}
+	state
 }
 
 /// A position.
@@ -33,103 +65,154 @@ struct Position {
 
 /// Current state of the head and tails positions.
 #[derive(Debug)]
-struct State<const KNOTS: usize> {
+struct State {
 	/// Head and tails position.
-	positions: [Position; KNOTS],
+	positions: Vec<Position>,
+	/// Positions visited by each knot so far.
+	visits: Visits,
 }
 
-impl<const KNOTS: usize> State<KNOTS> {
-	/// Create a new [State].
-	pub fn new() -> Self {
-		Self { positions: [Position::default(); KNOTS] }
+/// Positions visited by each knot of a rope.
+#[derive(Debug, Clone)]
+struct Visits {
+	/// Unique visited positions of each knot, including the start.
+	pub visited: Vec<HashSet<Position>>,
+	/// Number of steps each knot has moved. A diagonal move counts as one step.
+	pub distances: Vec<usize>,
+}
+
+impl Visits {
+	/// Unique positions visited by the tail.
+	pub fn tail(&self) -> &HashSet<Position> {
+		self.visited.last().expect("At least one knot must exist")
+	}
+}
+
+impl State {
+	/// Create a new [State] for a rope with the given number of knots,
+	/// including the head.
+	pub fn new(knots: usize) -> Self {
+		assert!(knots > 0, "At least one knot must exist");
+		let visits = Visits {
+			visited: vec![HashSet::from([Position::default()]); knots],
+			distances: vec![0; knots],
+		};
+		Self { positions: vec![Position::default(); knots], visits }
+	}
+
+	/// Positions visited by each knot so far.
+	pub fn visits(&self) -> &Visits {
+		&self.visits
 	}
 
-	/// Adjust the tails to the new position of the head.
+	/// Adjust the tails to the new position of the head. A knot that is not
+	/// touching its predecessor anymore moves one step towards it in each
+	/// direction.
 	pub fn adjust_tails(&mut self) {
-		for i_tail in 1..KNOTS {
-			let xdiff = (self.positions[i_tail].x - self.positions[i_tail - 1].x).abs();
-			let ydiff = (self.positions[i_tail].y - self.positions[i_tail - 1].y).abs();
-			if xdiff > 1 {
-				if ydiff > 0 {
-					// Move diagonally
-					self.positions[i_tail].x = self.positions[i_tail - 1].x
-						+ if self.positions[i_tail].x < self.positions[i_tail - 1].x {
-							-1
-						} else {
-							1
-						};
-					if ydiff > 1 {
-						self.positions[i_tail].y = self.positions[i_tail - 1].y
-							+ if self.positions[i_tail].y < self.positions[i_tail - 1].y {
-								-1
-							} else {
-								1
-							};
-					} else {
-						self.positions[i_tail].y = self.positions[i_tail - 1].y;
-					}
-				} else {
-					// Move horizontally
-					self.positions[i_tail].x = self.positions[i_tail - 1].x
-						+ if self.positions[i_tail].x < self.positions[i_tail - 1].x {
-							-1
-						} else {
-							1
-						};
-				}
-			} else if ydiff > 1 {
-				if xdiff > 0 {
-					// Move diagonally
-					if xdiff > 1 {
-						self.positions[i_tail].x = self.positions[i_tail - 1].x
-							+ if self.positions[i_tail].x < self.positions[i_tail - 1].x {
-								-1
-							} else {
-								1
-							};
-					} else {
-						self.positions[i_tail].x = self.positions[i_tail - 1].x;
-					}
-					self.positions[i_tail].y = self.positions[i_tail - 1].y
-						+ if self.positions[i_tail].y < self.positions[i_tail - 1].y {
-							-1
-						} else {
-							1
-						};
-				} else {
-					// Move vertically
-					self.positions[i_tail].y = self.positions[i_tail - 1].y
-						+ if self.positions[i_tail].y < self.positions[i_tail - 1].y {
-							-1
-						} else {
-							1
-						};
-				}
+		for i_tail in 1..self.positions.len() {
+			let leader = self.positions[i_tail - 1];
+			let knot = &mut self.positions[i_tail];
+			let xdiff = leader.x - knot.x;
+			let ydiff = leader.y - knot.y;
+			if xdiff.abs() <= 1 && ydiff.abs() <= 1 {
+				// Following knots cannot move either.
+				break;
 			}
+
+			knot.x += xdiff.signum();
+			knot.y += ydiff.signum();
+			self.visits.visited[i_tail].insert(*knot);
+			self.visits.distances[i_tail] += 1;
 		}
 	}
 
-	/// Run a series of head motions and return the tail's positions.
-	pub fn run_movements(&mut self, movements: &[Movement]) -> Vec<Position> {
-		let mut tail_positions =
-			vec![self.positions.last().copied().expect("At least one knot must exist")];
+	/// Label of the knot at the given index: `H` for the head, `T` for the tail
+	/// of a rope with two knots, the index otherwise.
+	fn knot_label(&self, index: usize) -> char {
+		match index {
+			0 => 'H',
+			1 if self.positions.len() == 2 => 'T',
+			index => u32::try_from(index).ok().and_then(|i| char::from_digit(i, 36)).unwrap_or('*'),
+		}
+	}
+
+	/// Render the knots in the puzzle's notation, where `s` denotes the start.
+	/// Knots closer to the head are drawn on top.
+	pub fn render(&self) -> String {
+		let bounds = Bounds::new(self.positions.iter().copied());
+		bounds.render(|position| {
+			self.positions
+				.iter()
+				.position(|knot| *knot == position)
+				.map(|index| self.knot_label(index))
+				.unwrap_or(if position == Position::default() { 's' } else { '.' })
+		})
+	}
+
+	/// Run a series of head motions and return the positions visited by each
+	/// knot so far.
+	pub fn run_movements(&mut self, movements: &[Movement]) -> &Visits {
 		for movement in movements {
 			for _ in 0..movement.steps {
 				// Move head.
-				match movement.direction {
-					Direction::Left => self.positions[0].x -= 1,
-					Direction::Right => self.positions[0].x += 1,
-					Direction::Up => self.positions[0].y -= 1,
-					Direction::Down => self.positions[0].y += 1,
-				}
+				let (x, y) = movement.direction.delta();
+				self.positions[0].x += x;
+				self.positions[0].y += y;
+				self.visits.visited[0].insert(self.positions[0]);
+				self.visits.distances[0] += 1;
 
 				// Move tail if it is too far.
 				self.adjust_tails();
-				tail_positions
-					.push(self.positions.last().copied().expect("At least one knot must exist"));
 			}
 		}
-		tail_positions
+		&self.visits
+	}
+}
+
+/// Render the visited positions as `#`, where `s` denotes the start.
+fn render_visited(visited: &HashSet<Position>) -> String {
+	let bounds = Bounds::new(visited.iter().copied());
+	bounds.render(|position| {
+		if position == Position::default() {
+			's'
+		} else if visited.contains(&position) {
+			'#'
+		} else {
+			'.'
+		}
+	})
+}
+
+/// Rectangular area containing the start and a set of positions.
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+	/// Top left corner.
+	min: Position,
+	/// Bottom right corner.
+	max: Position,
+}
+
+impl Bounds {
+	/// Compute the bounds containing the start and the given positions.
+	pub fn new(positions: impl IntoIterator<Item = Position>) -> Self {
+		let mut bounds = Self { min: Position::default(), max: Position::default() };
+		for position in positions {
+			bounds.min.x = bounds.min.x.min(position.x);
+			bounds.min.y = bounds.min.y.min(position.y);
+			bounds.max.x = bounds.max.x.max(position.x);
+			bounds.max.y = bounds.max.y.max(position.y);
+		}
+		bounds
+	}
+
+	/// Render the area line by line, using the given function to draw each cell.
+	pub fn render(&self, cell: impl Fn(Position) -> char) -> String {
+		let mut output = String::new();
+		for y in self.min.y..=self.max.y {
+			output.extend((self.min.x..=self.max.x).map(|x| cell(Position { x, y })));
+			output.push('\n');
+		}
+		output
 	}
 }
 
@@ -146,8 +229,10 @@ impl FromStr for Movement {
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
 		let (direction, steps) = s
-			.split_once(' ')
+			.trim()
+			.split_once(char::is_whitespace)
 			.ok_or_else(|| format!("`{s}` is an invalid movement! No space found!"))?;
+		let steps = steps.trim_start();
 
 		let direction = Direction::from_str(direction)?;
 		let steps = steps.parse().map_err(|err| format!("`{steps}` is not a number: {err}"))?;
@@ -156,12 +241,56 @@ impl FromStr for Movement {
 	}
 }
 
+impl Display for Movement {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} {}", self.direction, self.steps)
+	}
+}
+
 /// Direction of movement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Direction {
 	Left,
 	Right,
 	Up,
 	Down,
+	UpLeft,
+	UpRight,
+	DownLeft,
+	DownRight,
+}
+
+impl Direction {
+	/// Change of the x and y coordinates when moving one step in this
+	/// direction.
+	pub fn delta(self) -> (isize, isize) {
+		match self {
+			Self::Left => (-1, 0),
+			Self::Right => (1, 0),
+			Self::Up => (0, -1),
+			Self::Down => (0, 1),
+			Self::UpLeft => (-1, -1),
+			Self::UpRight => (1, -1),
+			Self::DownLeft => (-1, 1),
+			Self::DownRight => (1, 1),
+		}
+	}
+}
+
+impl Display for Direction {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let direction = match self {
+			Self::Left => "L",
+			Self::Right => "R",
+			Self::Up => "U",
+			Self::Down => "D",
+			Self::UpLeft => "UL",
+			Self::UpRight => "UR",
+			Self::DownLeft => "DL",
+			Self::DownRight => "DR",
+		};
+		f.write_str(direction)
+	}
 }
 
 impl FromStr for Direction {
@@ -173,7 +302,11 @@ impl FromStr for Direction {
 			"R" => Ok(Self::Right),
 			"U" => Ok(Self::Up),
 			"D" => Ok(Self::Down),
-			_ => Err(format!("`{s}` is not a valid direction in [LRUD]!")),
+			"UL" => Ok(Self::UpLeft),
+			"UR" => Ok(Self::UpRight),
+			"DL" => Ok(Self::DownLeft),
+			"DR" => Ok(Self::DownRight),
+			_ => Err(format!("`{s}` is not a valid direction in [L, R, U, D, UL, UR, DL, DR]!")),
 		}
 	}
 }
@@ -200,10 +333,9 @@ R 2"#;
 			.map(Movement::from_str)
 			.collect::<Result<_, _>>()
 			.expect("parsing");
-		let mut state = State::<2>::new();
-		let positions = state.run_movements(&movements);
-		let unique_positions: HashSet<Position> = positions.into_iter().collect();
-		assert_eq!(unique_positions.len(), 13);
+		let mut state = State::new(2);
+		let visits = state.run_movements(&movements);
+		assert_eq!(visits.tail().len(), 13);
 	}
 
 	const EXAMPLE_PART2: &str = r#"R 5
@@ -222,9 +354,60 @@ U 20"#;
 			.map(Movement::from_str)
 			.collect::<Result<_, _>>()
 			.expect("parsing");
-		let mut state = State::<10>::new();
-		let positions = state.run_movements(&movements);
-		let unique_positions: HashSet<Position> = positions.into_iter().collect();
-		assert_eq!(unique_positions.len(), 36);
+		let mut state = State::new(10);
+		let visits = state.run_movements(&movements);
+		assert_eq!(visits.tail().len(), 36);
+		let coverage: Vec<usize> = visits.visited.iter().map(HashSet::len).collect();
+		assert!(coverage.windows(2).all(|pair| pair[0] >= pair[1]));
+		assert_eq!(visits.distances[0], 96);
+	}
+
+	#[test]
+	fn diagonal_movements() {
+		let movements: Vec<Movement> = "UR 12\nDL  3\nR 1"
+			.lines()
+			.map(Movement::from_str)
+			.collect::<Result<_, _>>()
+			.expect("parsing");
+		assert_eq!(movements[0].direction, Direction::UpRight);
+		assert_eq!(movements[0].steps, 12);
+		assert_eq!(movements[1].steps, 3);
+
+		let mut state = State::new(3);
+		let visits = state.run_movements(&movements);
+		assert_eq!(visits.distances, [16, 12, 10]);
+		assert_eq!(state.positions[0], Position { x: 10, y: -9 });
+		assert_eq!(state.positions[2], Position { x: 10, y: -10 });
+	}
+
+	#[test]
+	fn rendering() {
+		let movements: Vec<Movement> = EXAMPLE_PART2
+			.lines()
+			.map(Movement::from_str)
+			.collect::<Result<_, _>>()
+			.expect("parsing");
+		let mut state = State::new(10);
+		state.run_movements(&movements[..2]);
+		assert_eq!(movements[1].to_string(), "U 8");
+		assert_eq!(
+			state.render(),
+			r#".....H
+.....1
+.....2
+.....3
+....54
+...6..
+..7...
+.8....
+9.....
+"#
+		);
+
+		assert_eq!(render_visited(state.visits().tail()), "s\n");
+
+		let mut state = State::new(2);
+		state.run_movements(&movements[..1]);
+		assert_eq!(state.render(), "s...TH\n");
 	}
 }
@@ -1,101 +1,870 @@
 //! Day 10.
 
-use std::str::FromStr;
+use std::{
+	collections::BTreeSet,
+	ffi::OsStr,
+	fmt::{self, Display},
+	io,
+	ops::ControlFlow,
+	path::Path,
+	str::FromStr,
+};
+
+use thiserror::Error;
 
 fn input() -> &'static str {
 	include_str!("day_10.txt")
 }
 
 pub fn run() {
-	let commands: Vec<Command> =
-		input().lines().map(Command::from_str).collect::<Result<_, _>>().expect("parsing");
-	let mut cpu = Cpu::default();
-	let states = cpu.run_program(&commands);
+	let program: Vec<Instruction> =
+		input().lines().map(Instruction::from_str).collect::<Result<_, _>>().expect("parsing");
+	let cpu = Cpu::default();
+
+	let crt = Crt::default();
 
 	println!("Part 1:");
-	let mut sum_of_signal_strengths = 0_i64;
-	for cycle in (20..=220).step_by(40) {
-		sum_of_signal_strengths += states[cycle - 1].register_x * cycle as i64;
-	}
+	let sum_of_signal_strengths = crt.signal_strength(&cpu, &program);
 	println!("Sum of signal strengths: {sum_of_signal_strengths}");
 	println!();
 
 	println!("Part 2:");
-	for y in 0..6 {
-		let mut line = String::new();
-		for x in 0..40 {
-			let cycle = y * 40 + x;
-			let c = if (x as i64 - states[cycle].register_x).abs() <= 1 { '#' } else { ' ' };
-			line.push(c);
+	let screen = crt.draw(&cpu, &program);
+	print!("{screen}");
+	let letters = screen.decode_letters().expect("reading letters");
+	println!("Letters on the screen: {letters}");
+}
+
+/// A CPU register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+	X,
+	Y,
+}
+
+impl Register {
+	/// Number of registers.
+	pub const COUNT: usize = 2;
+}
+
+impl FromStr for Register {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"x" => Ok(Self::X),
+			"y" => Ok(Self::Y),
+			_ => Err(format!("`{s}` is not a valid register in [x, y]!")),
 		}
-		println!("{line}");
 	}
 }
 
 /// The CPU in its current state.
-#[derive(Debug, Clone, Copy)]
-struct Cpu {
-	/// The X register.
-	register_x: i64,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cpu {
+	/// The registers, indexed by [Register].
+	registers: [i64; Register::COUNT],
+	/// Index of the next instruction to execute.
+	program_counter: usize,
+	/// Cycles already spent on the instruction at the program counter.
+	elapsed: usize,
 }
 
 impl Default for Cpu {
 	fn default() -> Self {
-		Self { register_x: 1 }
+		Self { registers: [1, 0], program_counter: 0, elapsed: 0 }
 	}
 }
 
 impl Cpu {
-	/// Execute the given command.
-	pub fn execute(&mut self, command: &Command) {
-		match command {
-			Command::Noop => (),
-			Command::AddX(val) => self.register_x += val,
-		}
+	/// Get the value of a register.
+	pub fn register(&self, register: Register) -> i64 {
+		self.registers[register as usize]
+	}
+
+	/// Get a mutable reference to a register.
+	pub fn register_mut(&mut self, register: Register) -> &mut i64 {
+		&mut self.registers[register as usize]
 	}
 
-	/// Run a program, returning the state at every cycle.
-	pub fn run_program(&mut self, commands: &[Command]) -> Vec<Self> {
-		let mut states = vec![*self];
-		for command in commands {
-			for _ in 1..command.cycles() {
-				states.push(*self);
+	/// Index of the next instruction to execute.
+	pub fn program_counter(&self) -> usize {
+		self.program_counter
+	}
+
+	/// Execute the given instruction, completing it and moving the program
+	/// counter to the next instruction. Arithmetic wraps around on overflow,
+	/// like on a real CPU.
+	pub fn execute(&mut self, instruction: &Instruction) {
+		let jump = match *instruction {
+			Instruction::Noop => None,
+			Instruction::AddX(value) => {
+				let x = self.register_mut(Register::X);
+				*x = x.wrapping_add(value);
+				None
+			}
+			Instruction::AddY(value) => {
+				let y = self.register_mut(Register::Y);
+				*y = y.wrapping_add(value);
+				None
+			}
+			Instruction::MulX(value) => {
+				let x = self.register_mut(Register::X);
+				*x = x.wrapping_mul(value);
+				None
+			}
+			Instruction::Jump(offset) => Some(offset),
+			Instruction::JumpIfZero(register, offset) => {
+				(self.register(register) == 0).then_some(offset)
+			}
+			Instruction::JumpIfNotZero(register, offset) => {
+				(self.register(register) != 0).then_some(offset)
+			}
+		};
+
+		self.elapsed = 0;
+		self.program_counter = match jump {
+			// Jumping before the start ends the program.
+			Some(offset) => self.program_counter.checked_add_signed(offset).unwrap_or(usize::MAX),
+			None => self.program_counter + 1,
+		};
+	}
+
+	/// Lazily run a program from this state until the program counter leaves
+	/// it, yielding the states during and after every cycle.
+	pub fn cycles<'a>(&self, program: &'a [Instruction]) -> Cycles<'a> {
+		Cycles { cpu: *self, program, cycle: 0 }
+	}
+
+	/// Run a program until the program counter leaves it. The hook is called
+	/// for every cycle with the 1-based cycle number and the state during that
+	/// cycle, and can stop the execution before the cycle completes. The cycles
+	/// already spent on the current instruction are kept, so running the
+	/// program again resumes with the stopped cycle.
+	pub fn run_program(
+		&mut self,
+		program: &[Instruction],
+		mut hook: impl FnMut(usize, &Self) -> ControlFlow<()>,
+	) {
+		for state in self.cycles(program) {
+			if hook(state.cycle, &state.during).is_break() {
+				*self = state.during;
+				return;
 			}
-			self.execute(command);
-			states.push(*self);
+			*self = state.after;
 		}
-		states
 	}
 }
 
-/// Assembly CPU command.
-enum Command {
+/// States of the CPU in a single cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CycleState {
+	/// Cycle number, starting at 1.
+	pub cycle: usize,
+	/// State during the cycle, before the current instruction completes.
+	pub during: Cpu,
+	/// State after the cycle, when an instruction completing in this cycle has
+	/// been executed.
+	pub after: Cpu,
+}
+
+/// Iterator running a program cycle by cycle.
+#[derive(Debug, Clone)]
+pub struct Cycles<'a> {
+	/// Current state of the CPU.
+	cpu: Cpu,
+	/// The program to run.
+	program: &'a [Instruction],
+	/// Number of the last cycle.
+	cycle: usize,
+}
+
+impl Iterator for Cycles<'_> {
+	type Item = CycleState;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let instruction = self.program.get(self.cpu.program_counter)?;
+
+		self.cycle += 1;
+		let during = self.cpu;
+		self.cpu.elapsed += 1;
+		if self.cpu.elapsed >= instruction.cycles() {
+			self.cpu.execute(instruction);
+		}
+		Some(CycleState { cycle: self.cycle, during, after: self.cpu })
+	}
+}
+
+/// Definition of an instruction in the [INSTRUCTION_TABLE].
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionInfo {
+	/// Name of the instruction in the assembly.
+	pub mnemonic: &'static str,
+	/// Number of cycles the instruction takes.
+	pub cycles: usize,
+	/// Parse the operands following the mnemonic. Returns `None` if the
+	/// number of operands does not match.
+	pub parse: fn(&[&str]) -> Option<Result<Instruction, String>>,
+}
+
+/// The instruction set.
+pub const INSTRUCTION_TABLE: [InstructionInfo; 7] = [
+	InstructionInfo {
+		mnemonic: "noop",
+		cycles: 1,
+		parse: |operands| matches!(operands, []).then_some(Ok(Instruction::Noop)),
+	},
+	InstructionInfo {
+		mnemonic: "addx",
+		cycles: 2,
+		parse: |operands| match operands {
+			[value] => Some(parse_number(value).map(Instruction::AddX)),
+			_ => None,
+		},
+	},
+	InstructionInfo {
+		mnemonic: "addy",
+		cycles: 2,
+		parse: |operands| match operands {
+			[value] => Some(parse_number(value).map(Instruction::AddY)),
+			_ => None,
+		},
+	},
+	InstructionInfo {
+		mnemonic: "mulx",
+		cycles: 3,
+		parse: |operands| match operands {
+			[value] => Some(parse_number(value).map(Instruction::MulX)),
+			_ => None,
+		},
+	},
+	InstructionInfo {
+		mnemonic: "jmp",
+		cycles: 1,
+		parse: |operands| match operands {
+			[offset] => Some(parse_offset(offset).map(Instruction::Jump)),
+			_ => None,
+		},
+	},
+	InstructionInfo {
+		mnemonic: "jz",
+		cycles: 2,
+		parse: |operands| match operands {
+			[register, offset] => Some(
+				parse_condition(register, offset)
+					.map(|(register, offset)| Instruction::JumpIfZero(register, offset)),
+			),
+			_ => None,
+		},
+	},
+	InstructionInfo {
+		mnemonic: "jnz",
+		cycles: 2,
+		parse: |operands| match operands {
+			[register, offset] => Some(
+				parse_condition(register, offset)
+					.map(|(register, offset)| Instruction::JumpIfNotZero(register, offset)),
+			),
+			_ => None,
+		},
+	},
+];
+
+/// Parse an immediate value operand.
+fn parse_number(number: &str) -> Result<i64, String> {
+	number.parse().map_err(|err| format!("Invalid number: {number} ({err})"))
+}
+
+/// Parse a jump offset operand.
+fn parse_offset(offset: &str) -> Result<isize, String> {
+	offset.parse().map_err(|err| format!("Invalid offset: {offset} ({err})"))
+}
+
+/// Parse the register and offset operands of a conditional jump.
+fn parse_condition(register: &str, offset: &str) -> Result<(Register, isize), String> {
+	Ok((register.parse()?, parse_offset(offset)?))
+}
+
+/// Assembly CPU instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
 	/// No operation.
 	Noop,
-	/// Add to the X register.
+	/// Add to the X register, wrapping around on overflow.
 	AddX(i64),
+	/// Add to the Y register, wrapping around on overflow.
+	AddY(i64),
+	/// Multiply the X register, wrapping around on overflow.
+	MulX(i64),
+	/// Jump relative to this instruction.
+	Jump(isize),
+	/// Jump relative to this instruction if the register is zero.
+	JumpIfZero(Register, isize),
+	/// Jump relative to this instruction if the register is not zero.
+	JumpIfNotZero(Register, isize),
 }
 
-impl Command {
-	/// Get the number of cycles this command takes.
-	pub fn cycles(&self) -> usize {
+impl Instruction {
+	/// Get the name of this instruction in the assembly.
+	pub fn mnemonic(&self) -> &'static str {
 		match self {
-			Self::Noop => 1,
-			Self::AddX(_) => 2,
+			Self::Noop => "noop",
+			Self::AddX(_) => "addx",
+			Self::AddY(_) => "addy",
+			Self::MulX(_) => "mulx",
+			Self::Jump(_) => "jmp",
+			Self::JumpIfZero(..) => "jz",
+			Self::JumpIfNotZero(..) => "jnz",
 		}
 	}
+
+	/// Get the entry of this instruction in the [INSTRUCTION_TABLE].
+	pub fn info(&self) -> &'static InstructionInfo {
+		let mnemonic = self.mnemonic();
+		INSTRUCTION_TABLE
+			.iter()
+			.find(|info| info.mnemonic == mnemonic)
+			.expect("every instruction is in the table")
+	}
+
+	/// Get the number of cycles this instruction takes.
+	pub fn cycles(&self) -> usize {
+		self.info().cycles
+	}
 }
 
-impl FromStr for Command {
+impl FromStr for Instruction {
 	type Err = String;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		match s.split_once(' ') {
-			Some((left, right)) if left == "addx" => Ok(Self::AddX(
-				right.parse().map_err(|err| format!("Invalid number: {right} ({err})"))?,
-			)),
-			None if s == "noop" => Ok(Self::Noop),
-			_ => Err(format!("Invalid input: {s}")),
+		let arguments: Vec<&str> = s.split_whitespace().collect();
+		let Some((&mnemonic, operands)) = arguments.split_first() else {
+			return Err(format!("Invalid input: {s}"));
+		};
+		INSTRUCTION_TABLE
+			.iter()
+			.find(|info| info.mnemonic == mnemonic)
+			.and_then(|info| (info.parse)(operands))
+			.unwrap_or_else(|| Err(format!("Invalid input: {s}")))
+	}
+}
+
+/// A CRT display driven by the X register of the CPU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crt {
+	/// Number of pixels per row.
+	pub width: usize,
+	/// Number of rows.
+	pub height: usize,
+	/// Width of the sprite. It starts `(sprite_width - 1) / 2` pixels left of
+	/// the X register.
+	pub sprite_width: usize,
+	/// Cycles during which the signal strength is sampled.
+	pub signal_cycles: BTreeSet<usize>,
+}
+
+impl Default for Crt {
+	fn default() -> Self {
+		Self {
+			width: 40,
+			height: 6,
+			sprite_width: 3,
+			signal_cycles: (20..=220).step_by(40).collect(),
 		}
 	}
 }
+
+impl Crt {
+	/// Sum up the signal strengths, the cycle number times the X register,
+	/// during the sampled cycles of the program, wrapping around on overflow.
+	pub fn signal_strength(&self, cpu: &Cpu, program: &[Instruction]) -> i64 {
+		let Some(&last) = self.signal_cycles.last() else {
+			return 0;
+		};
+
+		cpu.cycles(program)
+			.take(last)
+			.filter(|state| self.signal_cycles.contains(&state.cycle))
+			.map(|state| state.during.register(Register::X).wrapping_mul(state.cycle as i64))
+			.fold(0, i64::wrapping_add)
+	}
+
+	/// Draw the screen, one pixel per cycle row by row. A pixel is lit if the
+	/// sprite covers it during that cycle.
+	pub fn draw(&self, cpu: &Cpu, program: &[Instruction]) -> Bitmap {
+		let mut screen = Bitmap::new(self.width, self.height);
+		// Wide enough that sprites at the edges of the register's range can't
+		// overflow.
+		let sprite_left = (self.sprite_width.max(1) as i128 - 1) / 2;
+		for state in cpu.cycles(program).take(self.width * self.height) {
+			let (x, y) = ((state.cycle - 1) % self.width, (state.cycle - 1) / self.width);
+			let sprite_start = i128::from(state.during.register(Register::X)) - sprite_left;
+			let lit =
+				(sprite_start..sprite_start + self.sprite_width as i128).contains(&(x as i128));
+			screen.set(x, y, lit);
+		}
+		screen
+	}
+}
+
+/// A monochrome image, stored row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+	/// Number of pixels per row.
+	width: usize,
+	/// Number of rows.
+	height: usize,
+	/// Whether each pixel is lit.
+	pixels: Vec<bool>,
+}
+
+impl Bitmap {
+	/// Create a new [Bitmap] with all pixels off.
+	pub fn new(width: usize, height: usize) -> Self {
+		Self { width, height, pixels: vec![false; width * height] }
+	}
+
+	/// Get whether the pixel is lit.
+	pub fn get(&self, x: usize, y: usize) -> bool {
+		self.pixels[y * self.width + x]
+	}
+
+	/// Set whether the pixel is lit.
+	pub fn set(&mut self, x: usize, y: usize, lit: bool) {
+		self.pixels[y * self.width + x] = lit;
+	}
+
+	/// Copy out the area of the given size at the given position.
+	pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+		let mut cropped = Self::new(width, height);
+		for dy in 0..height {
+			for dx in 0..width {
+				cropped.set(dx, dy, self.get(x + dx, y + dy));
+			}
+		}
+		cropped
+	}
+
+	/// Read the capital letters written in the standard 4x6 AoC font, each
+	/// followed by one column of spacing.
+	pub fn decode_letters(&self) -> Result<String, OcrError> {
+		if self.height != GLYPH_HEIGHT {
+			return Err(OcrError::WrongHeight(self.height));
+		}
+
+		(0..self.width.div_ceil(GLYPH_WIDTH + 1))
+			.map(|position| {
+				let x = position * (GLYPH_WIDTH + 1);
+				let glyph = self.crop(x, 0, GLYPH_WIDTH.min(self.width - x), GLYPH_HEIGHT);
+				FONT.iter()
+					.find(|(_, rows)| {
+						rows.iter().enumerate().all(|(y, row)| {
+							row.chars()
+								.enumerate()
+								.all(|(x, c)| x < glyph.width && glyph.get(x, y) == (c == '#'))
+						})
+					})
+					.map(|(letter, _)| *letter)
+					.ok_or(OcrError::UnknownGlyph { position, glyph })
+			})
+			.collect()
+	}
+
+	/// Render the bitmap as text, line by line, using the given characters for
+	/// lit and dark pixels.
+	pub fn render(&self, lit: char, dark: char) -> String {
+		let mut output = String::new();
+		for row in self.pixels.chunks(self.width.max(1)) {
+			output.extend(row.iter().map(|pixel| if *pixel { lit } else { dark }));
+			output.push('\n');
+		}
+		output
+	}
+
+	/// Encode the bitmap as plain PBM image, lit pixels being black.
+	pub fn to_pbm(&self) -> String {
+		format!("P1\n{} {}\n{}", self.width, self.height, self.render_rows("1", "0"))
+	}
+
+	/// Encode the bitmap as plain PGM image, lit pixels being black on white.
+	pub fn to_pgm(&self) -> String {
+		format!("P2\n{} {}\n255\n{}", self.width, self.height, self.render_rows("0", "255"))
+	}
+
+	/// Write the bitmap to an image file, choosing PBM or PGM by the file
+	/// extension.
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		let image = match path.extension().and_then(OsStr::to_str) {
+			Some("pbm") => self.to_pbm(),
+			Some("pgm") => self.to_pgm(),
+			_ => {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					format!("`{}` is neither a .pbm nor a .pgm file", path.display()),
+				));
+			}
+		};
+		std::fs::write(path, image)
+	}
+
+	/// Render the pixel values as space separated rows.
+	fn render_rows(&self, lit: &str, dark: &str) -> String {
+		let mut output = String::new();
+		for row in self.pixels.chunks(self.width.max(1)) {
+			let values: Vec<&str> =
+				row.iter().map(|pixel| if *pixel { lit } else { dark }).collect();
+			output.push_str(&values.join(" "));
+			output.push('\n');
+		}
+		output
+	}
+}
+
+impl Display for Bitmap {
+	/// Draw the bitmap with `#` for lit and `.` for dark pixels.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.render('#', '.'))
+	}
+}
+
+/// Width of a glyph of the AoC font.
+const GLYPH_WIDTH: usize = 4;
+/// Height of a glyph of the AoC font.
+const GLYPH_HEIGHT: usize = 6;
+
+/// The known letters of the AoC font.
+const FONT: [(char, [&str; GLYPH_HEIGHT]); 17] = [
+	('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+	('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+	('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+	('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+	('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+	('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+	('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+	('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+	('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+	('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+	('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+	('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+	('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+	('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+	('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+	('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+	('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Error while reading letters from a bitmap.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum OcrError {
+	#[error("Letters must be {GLYPH_HEIGHT} pixels high, but the bitmap is {0} pixels high")]
+	WrongHeight(usize),
+	#[error("Unknown glyph at letter position {position}:\n{glyph}")]
+	UnknownGlyph { position: usize, glyph: Bitmap },
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn extended_instructions() {
+		// Computes x = 2^5 in a loop, counting y down from 5.
+		let program: Vec<Instruction> = "addy 5\nmulx 2\naddy -1\njnz y -2\nnoop"
+			.lines()
+			.map(Instruction::from_str)
+			.collect::<Result<_, _>>()
+			.expect("parsing");
+
+		let mut cpu = Cpu::default();
+		let mut cycles = 0;
+		cpu.run_program(&program, |cycle, _cpu| {
+			cycles = cycle;
+			ControlFlow::Continue(())
+		});
+		assert_eq!(cpu.register(Register::X), 32);
+		assert_eq!(cpu.register(Register::Y), 0);
+		assert_eq!(cycles, 2 + 5 * (3 + 2 + 2) + 1);
+		assert!(program.iter().all(|instruction| {
+			let info = instruction.info();
+			info.mnemonic == instruction.mnemonic() && info.cycles == instruction.cycles()
+		}));
+		assert_eq!("jz x 2".parse(), Ok(Instruction::JumpIfZero(Register::X, 2)));
+		assert_eq!("noop 1".parse::<Instruction>(), Err("Invalid input: noop 1".to_owned()));
+		assert!("jnz z 2".parse::<Instruction>().is_err());
+
+		let mut cpu = Cpu::default();
+		let program = [Instruction::AddX(1), Instruction::Jump(-1)];
+		cpu.run_program(&program, |cycle, _cpu| {
+			if cycle > 100 {
+				ControlFlow::Break(())
+			} else {
+				ControlFlow::Continue(())
+			}
+		});
+		assert_eq!(cpu.register(Register::X), 34);
+
+		// Doubles x until it overflows to zero.
+		let mut cpu = Cpu::default();
+		let program = [Instruction::MulX(2), Instruction::Jump(-1)];
+		cpu.run_program(&program, |cycle, _cpu| {
+			if cycle > 400 {
+				ControlFlow::Break(())
+			} else {
+				ControlFlow::Continue(())
+			}
+		});
+		assert_eq!(cpu.register(Register::X), 0);
+
+		// Stopping within an instruction and resuming keeps the cycle count.
+		let program = [Instruction::MulX(3), Instruction::AddX(1)];
+		let mut cpu = Cpu::default();
+		cpu.run_program(&program, |cycle, _cpu| {
+			if cycle == 2 {
+				ControlFlow::Break(())
+			} else {
+				ControlFlow::Continue(())
+			}
+		});
+		assert_eq!((cpu.program_counter(), cpu.register(Register::X)), (0, 1));
+		let mut cycles = 0;
+		cpu.run_program(&program, |_cycle, _cpu| {
+			cycles += 1;
+			ControlFlow::Continue(())
+		});
+		assert_eq!(cycles, 4);
+		assert_eq!(cpu.register(Register::X), 4);
+	}
+
+	#[test]
+	fn cycle_states() {
+		let program: Vec<Instruction> = "noop\naddx 3\naddx -5"
+			.lines()
+			.map(Instruction::from_str)
+			.collect::<Result<_, _>>()
+			.expect("parsing");
+		let registers: Vec<(usize, i64, i64)> = Cpu::default()
+			.cycles(&program)
+			.map(|state| {
+				(state.cycle, state.during.register(Register::X), state.after.register(Register::X))
+			})
+			.collect();
+		assert_eq!(registers, [(1, 1, 1), (2, 1, 1), (3, 1, 4), (4, 4, 4), (5, 4, -1)]);
+
+		let looping = [Instruction::Jump(0)];
+		let last = Cpu::default().cycles(&looping).nth(9_999_999).expect("endless loop");
+		assert_eq!(last.cycle, 10_000_000);
+	}
+
+	#[test]
+	fn letter_recognition() {
+		let mut bitmap = Bitmap::new(14, 6);
+		for (x, rows) in [(0, FONT[0].1), (5, FONT[14].1), (10, FONT[6].1)] {
+			for (y, row) in rows.iter().enumerate() {
+				for (dx, c) in row.chars().enumerate() {
+					bitmap.set(x + dx, y, c == '#');
+				}
+			}
+		}
+		assert_eq!(bitmap.decode_letters(), Ok("ASH".to_owned()));
+
+		bitmap.set(1, 1, true);
+		let err = bitmap.decode_letters().unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"Unknown glyph at letter position 0:\n.##.\n##.#\n#..#\n####\n#..#\n#..#\n"
+		);
+	}
+
+	/// Example program.
+	const EXAMPLE: &str = r#"addx 15
+addx -11
+addx 6
+addx -3
+addx 5
+addx -1
+addx -8
+addx 13
+addx 4
+noop
+addx -1
+addx 5
+addx -1
+addx 5
+addx -1
+addx 5
+addx -1
+addx 5
+addx -1
+addx -35
+addx 1
+addx 24
+addx -19
+addx 1
+addx 16
+addx -11
+noop
+noop
+addx 21
+addx -15
+noop
+noop
+addx -3
+addx 9
+addx 1
+addx -3
+addx 8
+addx 1
+addx 5
+noop
+noop
+noop
+noop
+noop
+addx -36
+noop
+addx 1
+addx 7
+noop
+noop
+noop
+addx 2
+addx 6
+noop
+noop
+noop
+noop
+noop
+addx 1
+noop
+noop
+addx 7
+addx 1
+noop
+addx -13
+addx 13
+addx 7
+noop
+addx 1
+addx -33
+noop
+noop
+noop
+addx 2
+noop
+noop
+noop
+addx 8
+noop
+addx -1
+addx 2
+addx 1
+noop
+addx 17
+addx -9
+addx 1
+addx 1
+addx -3
+addx 11
+noop
+noop
+addx 1
+noop
+addx 1
+noop
+noop
+addx -13
+addx -19
+addx 1
+addx 3
+addx 26
+addx -30
+addx 12
+addx -1
+addx 3
+addx 1
+noop
+noop
+noop
+addx -9
+addx 18
+addx 1
+addx 2
+noop
+noop
+addx 9
+noop
+noop
+noop
+addx -1
+addx 2
+addx -37
+addx 1
+addx 3
+noop
+addx 15
+addx -21
+addx 22
+addx -6
+addx 1
+noop
+addx 2
+addx 1
+noop
+addx -10
+noop
+noop
+addx 20
+addx 1
+addx 2
+addx 2
+addx -6
+addx -11
+noop
+noop
+noop"#;
+
+	#[test]
+	fn example() {
+		let program: Vec<Instruction> =
+			EXAMPLE.lines().map(Instruction::from_str).collect::<Result<_, _>>().expect("parsing");
+		let cpu = Cpu::default();
+		let crt = Crt::default();
+		assert_eq!(crt.signal_strength(&cpu, &program), 13140);
+		assert_eq!(
+			crt.draw(&cpu, &program).render('#', '.'),
+			r#"##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....
+"#
+		);
+
+		let crt =
+			Crt { width: 8, height: 2, sprite_width: 1, signal_cycles: BTreeSet::from([1, 3]) };
+		assert_eq!(crt.signal_strength(&cpu, &program), 1 + 3 * 16);
+		let screen = crt.draw(&cpu, &program);
+		assert_eq!(screen.render('X', ' '), " X   X  \n        \n");
+		assert_eq!(screen.to_pbm(), "P1\n8 2\n0 1 0 0 0 1 0 0\n0 0 0 0 0 0 0 0\n");
+		assert!(screen.to_pgm().starts_with("P2\n8 2\n255\n255 0 255"));
+		assert!(screen.save(Path::new("screen.png")).is_err());
+	}
+
+	#[test]
+	fn register_overflow() {
+		let program: Vec<Instruction> = "addx 9223372036854775806\nnoop\nnoop"
+			.lines()
+			.map(Instruction::from_str)
+			.collect::<Result<_, _>>()
+			.expect("parsing");
+		let cpu = Cpu::default();
+		let crt =
+			Crt { width: 4, height: 1, sprite_width: 3, signal_cycles: BTreeSet::from([2, 3, 4]) };
+		assert_eq!(crt.draw(&cpu, &program).render('#', '.'), "##..\n");
+		assert_eq!(
+			crt.signal_strength(&cpu, &program),
+			2i64.wrapping_add(i64::MAX.wrapping_mul(3)).wrapping_add(i64::MAX.wrapping_mul(4))
+		);
+	}
+}
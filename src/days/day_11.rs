@@ -65,7 +65,7 @@ impl Monkeys {
 			while let Some(item) = self[i].items.pop_front() {
 				self[i].handled += 1;
 				let worry_level = self[i].operation.compute(item) / div_by % common_ground;
-				let pass_to = if worry_level % self[i].test_divisible_by == 0 {
+				let pass_to = if worry_level.is_multiple_of(self[i].test_divisible_by) {
 					self[i].if_true
 				} else {
 					self[i].if_false
@@ -177,11 +177,11 @@ fn parse_monkeys(input: &str) -> Monkeys {
 	monkeys
 }
 
-fn nom_monkeys(input: &str) -> MyIResult<Monkeys> {
+fn nom_monkeys(input: &str) -> MyIResult<'_, Monkeys> {
 	map(many1(delimited(multispace0, nom_monkey, multispace0)), Monkeys)(input)
 }
 
-fn nom_monkey(input: &str) -> MyIResult<Monkey> {
+fn nom_monkey(input: &str) -> MyIResult<'_, Monkey> {
 	let (input, _) = tag_no_case("Monkey ")(input)?;
 	let (input, num) =
 		terminated(map_res(digit1, |digit: &str| digit.parse()), nom_char(':'))(input)?;
@@ -219,7 +219,7 @@ fn nom_monkey(input: &str) -> MyIResult<Monkey> {
 	))
 }
 
-fn nom_operation(input: &str) -> MyIResult<Operation> {
+fn nom_operation(input: &str) -> MyIResult<'_, Operation> {
 	let (input, _) = tag_no_case("Operation: new = ")(input)?;
 	let (input, a) = nom_value(input)?;
 	let (input, _) = multispace0(input)?;
@@ -229,17 +229,17 @@ fn nom_operation(input: &str) -> MyIResult<Operation> {
 	Ok((output, Operation { a, operator, b }))
 }
 
-fn nom_value(input: &str) -> MyIResult<Value> {
+fn nom_value(input: &str) -> MyIResult<'_, Value> {
 	let old = map(tag_no_case("old"), |_| Value::Old);
 	let number = map(nom_number, Value::Number);
 	alt((old, number))(input)
 }
 
-fn nom_number(input: &str) -> MyIResult<Number> {
+fn nom_number(input: &str) -> MyIResult<'_, Number> {
 	map_res(digit1, |digit: &str| digit.parse())(input)
 }
 
-fn nom_operator(input: &str) -> MyIResult<Operator> {
+fn nom_operator(input: &str) -> MyIResult<'_, Operator> {
 	let add = map(nom_char('+'), |_| Operator::Add);
 	let multiply = map(nom_char('*'), |_| Operator::Multiply);
 	alt((add, multiply))(input)
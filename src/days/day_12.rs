@@ -1,10 +1,14 @@
 //! Day 12.
 
 use std::{
-	collections::{HashMap, VecDeque},
+	cmp::{Ordering, Reverse},
+	collections::{hash_map::Entry, BinaryHeap, HashMap, VecDeque},
+	fmt::{self, Display},
 	str::FromStr,
 };
 
+use thiserror::Error;
+
 fn input() -> &'static str {
 	include_str!("day_12.txt")
 }
@@ -13,26 +17,17 @@ pub fn run() {
 	let grid: Grid = input().parse().expect("parsing");
 
 	println!("Part 1:");
-	let shortest_way = grid.find_shortest_way_from(grid.start);
-	println!("Shortest way: {shortest_way}");
+	let shortest_way = grid.find_shortest_way_from(grid.start).expect("no way found");
+	println!("Shortest way: {}", shortest_way.len() - 1);
 	println!();
 
 	println!("Part 2:");
-	let mut minimum = usize::MAX;
-	for y in 0..grid.height() {
-		for x in 0..grid.width() {
-			let pos = Position { x, y };
-			if grid.get(pos) == 0 {
-				let length = grid.find_shortest_way_from(pos);
-				minimum = minimum.min(length);
-			}
-		}
-	}
+	let (_, minimum) = grid.nearest_start_with_height(0).expect("no way found");
 	println!("Shortest way: {minimum}");
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-struct Position {
+pub struct Position {
 	pub x: usize,
 	pub y: usize,
 }
@@ -44,19 +39,164 @@ impl Position {
 			Direction::Right => Self { x: self.x + 1, y: self.y },
 			Direction::Down => Self { x: self.x, y: self.y + 1 },
 			Direction::Left => Self { x: self.x - 1, y: self.y },
+			Direction::UpRight => Self { x: self.x + 1, y: self.y - 1 },
+			Direction::DownRight => Self { x: self.x + 1, y: self.y + 1 },
+			Direction::DownLeft => Self { x: self.x - 1, y: self.y + 1 },
+			Direction::UpLeft => Self { x: self.x - 1, y: self.y - 1 },
+		}
+	}
+
+	/// Number of steps to another position if moving diagonally is allowed or
+	/// not. Never overestimates the number of steps on the grid.
+	pub fn steps_to(&self, other: Position, diagonal: bool) -> usize {
+		let dx = self.x.abs_diff(other.x);
+		let dy = self.y.abs_diff(other.y);
+		if diagonal {
+			dx.max(dy)
+		} else {
+			dx + dy
 		}
 	}
 }
 
-enum Direction {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
 	Up,
 	Right,
 	Down,
 	Left,
+	UpRight,
+	DownRight,
+	DownLeft,
+	UpLeft,
+}
+
+impl Direction {
+	/// Get the direction of a step between two neighboring positions.
+	pub fn between(from: Position, to: Position) -> Option<Self> {
+		match (to.x.cmp(&from.x), to.y.cmp(&from.y)) {
+			(Ordering::Equal, Ordering::Less) => Some(Self::Up),
+			(Ordering::Greater, Ordering::Equal) => Some(Self::Right),
+			(Ordering::Equal, Ordering::Greater) => Some(Self::Down),
+			(Ordering::Less, Ordering::Equal) => Some(Self::Left),
+			(Ordering::Greater, Ordering::Less) => Some(Self::UpRight),
+			(Ordering::Greater, Ordering::Greater) => Some(Self::DownRight),
+			(Ordering::Less, Ordering::Greater) => Some(Self::DownLeft),
+			(Ordering::Less, Ordering::Less) => Some(Self::UpLeft),
+			(Ordering::Equal, Ordering::Equal) => None,
+		}
+	}
+
+	/// Arrow symbol for this direction.
+	pub fn arrow(self) -> char {
+		match self {
+			Self::Up => '^',
+			Self::Right => '>',
+			Self::Down => 'v',
+			Self::Left => '<',
+			Self::UpRight | Self::DownLeft => '/',
+			Self::DownRight | Self::UpLeft => '\\',
+		}
+	}
+}
+
+/// Rules for moving between neighboring positions of a heightmap.
+pub trait MovementRules {
+	/// Whether moving from a position of height `from` to a neighbor of height
+	/// `to` is allowed.
+	fn allows(&self, from: u8, to: u8) -> bool;
+
+	/// Cost of moving from a position of height `from` to a neighbor of height
+	/// `to`. Only called for allowed moves.
+	fn cost(&self, from: u8, to: u8) -> usize;
+
+	/// Lower bound of the cost of any move, used for the A* heuristic.
+	fn min_cost(&self) -> usize;
+
+	/// Whether diagonal neighbors can be reached in one move.
+	fn diagonal(&self) -> bool {
+		false
+	}
+}
+
+/// The puzzle's rules: climb at most one level, drop any number of levels,
+/// every move costs 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PuzzleRules;
+
+impl MovementRules for PuzzleRules {
+	fn allows(&self, from: u8, to: u8) -> bool {
+		to <= from + 1
+	}
+
+	fn cost(&self, _from: u8, _to: u8) -> usize {
+		1
+	}
+
+	fn min_cost(&self) -> usize {
+		1
+	}
+}
+
+/// Rules for hiking routes, where climbing costs more than walking on the
+/// flat.
+#[derive(Debug, Clone, Copy)]
+pub struct HikingRules {
+	/// Maximum number of levels that can be climbed in one move.
+	pub max_climb: u8,
+	/// Maximum number of levels that can be dropped in one move, unlimited if
+	/// `None`.
+	pub max_drop: Option<u8>,
+	/// Cost of a move between positions of the same height.
+	pub flat_cost: usize,
+	/// Additional cost for each level climbed.
+	pub climb_cost: usize,
+	/// Additional cost for each level dropped.
+	pub drop_cost: usize,
+	/// Whether diagonal moves are allowed.
+	pub diagonal: bool,
+}
+
+impl Default for HikingRules {
+	fn default() -> Self {
+		Self {
+			max_climb: 1,
+			max_drop: None,
+			flat_cost: 1,
+			climb_cost: 0,
+			drop_cost: 0,
+			diagonal: false,
+		}
+	}
+}
+
+impl MovementRules for HikingRules {
+	fn allows(&self, from: u8, to: u8) -> bool {
+		if to >= from {
+			to - from <= self.max_climb
+		} else {
+			self.max_drop.is_none_or(|max_drop| from - to <= max_drop)
+		}
+	}
+
+	fn cost(&self, from: u8, to: u8) -> usize {
+		let levels = usize::from(from.abs_diff(to));
+		let per_level = if to >= from { self.climb_cost } else { self.drop_cost };
+		self.flat_cost + per_level * levels
+	}
+
+	fn min_cost(&self) -> usize {
+		self.flat_cost
+	}
+
+	fn diagonal(&self) -> bool {
+		self.diagonal
+	}
 }
 
 /// The map as a grid of heights.
-struct Grid {
+#[derive(Debug, Clone)]
+pub struct Grid {
 	width: usize,
 	height: Vec<u8>,
 	start: Position,
@@ -77,77 +217,453 @@ impl Grid {
 		self.height[pos.y * self.width + pos.x]
 	}
 
+	/// Get the directions in which a neighboring position exists, including
+	/// diagonal neighbors if requested.
+	fn neighbor_directions(&self, pos: Position, diagonal: bool) -> Vec<Direction> {
+		let up = pos.y > 0;
+		let right = pos.x < self.width() - 1;
+		let down = pos.y < self.height() - 1;
+		let left = pos.x > 0;
+
+		let mut candidates = vec![
+			(up, Direction::Up),
+			(right, Direction::Right),
+			(down, Direction::Down),
+			(left, Direction::Left),
+		];
+		if diagonal {
+			candidates.extend([
+				(up && right, Direction::UpRight),
+				(down && right, Direction::DownRight),
+				(down && left, Direction::DownLeft),
+				(up && left, Direction::UpLeft),
+			]);
+		}
+		candidates
+			.into_iter()
+			.filter_map(|(exists, direction)| exists.then_some(direction))
+			.collect()
+	}
+
 	/// Get the possible next directions from this position. Step size is the
 	/// height difference that can be passed upwards.
 	pub fn possible_directions(&self, pos: Position, step_size: u8) -> Vec<Direction> {
-		let mut directions = Vec::new();
 		let value = self.get(pos) + step_size;
+		let mut directions = self.neighbor_directions(pos, false);
+		directions.retain(|direction| self.get(pos.go(*direction)) <= value);
+		directions
+	}
 
-		if pos.y > 0 && self.get(Position { x: pos.x, y: pos.y - 1 }) <= value {
-			directions.push(Direction::Up);
-		}
-		if pos.x < self.width() - 1 && self.get(Position { x: pos.x + 1, y: pos.y }) <= value {
-			directions.push(Direction::Right);
-		}
-		if pos.y < self.height() - 1 && self.get(Position { x: pos.x, y: pos.y + 1 }) <= value {
-			directions.push(Direction::Down);
-		}
-		if pos.x > 0 && self.get(Position { x: pos.x - 1, y: pos.y }) <= value {
-			directions.push(Direction::Left);
+	/// Get the directions from which this position can be reached. Step size
+	/// is the height difference that can be passed upwards.
+	pub fn possible_directions_reverse(&self, pos: Position, step_size: u8) -> Vec<Direction> {
+		let value = self.get(pos);
+		let mut directions = self.neighbor_directions(pos, false);
+		directions.retain(|direction| value <= self.get(pos.go(*direction)) + step_size);
+		directions
+	}
+
+	/// Compute the length of the shortest way to the end from every position,
+	/// using a single breadth-first search backwards from the end.
+	pub fn distances_to_end(&self) -> DistanceField {
+		self.search_backwards(|_, _| false)
+	}
+
+	/// Find the position of the given height that is closest to the end and
+	/// return it together with the length of its shortest way.
+	pub fn nearest_start_with_height(&self, height: u8) -> Option<(Position, usize)> {
+		let mut nearest = None;
+		self.search_backwards(|pos, distance| {
+			let found = self.get(pos) == height;
+			if found {
+				nearest = Some((pos, distance));
+			}
+			found
+		});
+		nearest
+	}
+
+	/// Breadth-first search backwards from the end, calling `stop` for every
+	/// reached position with its distance until it returns `true`.
+	fn search_backwards(&self, mut stop: impl FnMut(Position, usize) -> bool) -> DistanceField {
+		let mut field =
+			DistanceField { width: self.width, distances: vec![None; self.height.len()] };
+		field.set(self.end, 0);
+		let mut queue = VecDeque::new();
+		queue.push_back(self.end);
+
+		while let Some(current) = queue.pop_front() {
+			let distance = field.get(current).expect("queued positions have a distance");
+			if stop(current, distance) {
+				break;
+			}
+
+			for direction in self.possible_directions_reverse(current, 1) {
+				let new_pos = current.go(direction);
+				if field.get(new_pos).is_none() {
+					field.set(new_pos, distance + 1);
+					queue.push_back(new_pos);
+				}
+			}
 		}
 
-		directions
+		field
 	}
 
-	/// Find the shortest way and return its length. `usize::MAX` means there is
-	/// no way to the end.
-	pub fn find_shortest_way_from(&self, start: Position) -> usize {
+	/// Find the shortest way to the end and return the positions along it,
+	/// including start and end. Returns `None` if there is no way to the end.
+	pub fn find_shortest_way_from(&self, start: Position) -> Option<Vec<Position>> {
+		// Maps visited positions to the position they were reached from.
 		let mut visited = HashMap::new();
-		visited.insert(start, 0);
+		visited.insert(start, start);
 		let mut queue = VecDeque::new();
 		queue.push_back(start);
 
 		while let Some(current) = queue.pop_front() {
+			if current == self.end {
+				break;
+			}
+
 			for direction in self.possible_directions(current, 1) {
 				let new_pos = current.go(direction);
-
-				#[allow(clippy::map_entry)]
-				if !visited.contains_key(&new_pos) {
-					visited.insert(new_pos, visited[&current] + 1);
+				if let Entry::Vacant(entry) = visited.entry(new_pos) {
+					entry.insert(current);
 					queue.push_back(new_pos);
 				}
 			}
 		}
 
-		visited.get(&self.end).copied().unwrap_or(usize::MAX)
+		let mut way = vec![self.end];
+		let mut current = self.end;
+		while current != start {
+			current = *visited.get(&current)?;
+			way.push(current);
+		}
+		way.reverse();
+		Some(way)
+	}
+
+	/// Find the cheapest way to the end under the given movement rules using
+	/// A*. Returns the total cost and the positions along the way, including
+	/// start and end, or `None` if there is no way to the end.
+	pub fn find_cheapest_way_from(
+		&self,
+		start: Position,
+		rules: &dyn MovementRules,
+	) -> Option<(usize, Vec<Position>)> {
+		let diagonal = rules.diagonal();
+		let heuristic = |pos: Position| pos.steps_to(self.end, diagonal) * rules.min_cost();
+		let index = |pos: Position| pos.y * self.width + pos.x;
+
+		// Maps reached positions to their cost so far and the position they were
+		// reached from.
+		let mut reached = HashMap::new();
+		reached.insert(start, (0, start));
+		let mut queue = BinaryHeap::new();
+		queue.push(Reverse((heuristic(start), 0, index(start))));
+
+		while let Some(Reverse((_, cost, current))) = queue.pop() {
+			let current = Position { x: current % self.width, y: current / self.width };
+			if current == self.end {
+				break;
+			}
+			if reached.get(&current).is_some_and(|(best, _)| *best < cost) {
+				// Outdated queue entry.
+				continue;
+			}
+
+			let height = self.get(current);
+			for direction in self.neighbor_directions(current, diagonal) {
+				let new_pos = current.go(direction);
+				let new_height = self.get(new_pos);
+				if !rules.allows(height, new_height) {
+					continue;
+				}
+
+				let new_cost = cost + rules.cost(height, new_height);
+				if reached.get(&new_pos).is_none_or(|(best, _)| new_cost < *best) {
+					reached.insert(new_pos, (new_cost, current));
+					queue.push(Reverse((new_cost + heuristic(new_pos), new_cost, index(new_pos))));
+				}
+			}
+		}
+
+		let (total, _) = *reached.get(&self.end)?;
+		let mut way = vec![self.end];
+		let mut current = self.end;
+		while current != start {
+			current = reached[&current].1;
+			way.push(current);
+		}
+		way.reverse();
+		Some((total, way))
+	}
+
+	/// Render the way on the map like the puzzle's illustration: each position
+	/// on the way shows the direction to the next one, the end is marked `E`.
+	pub fn render_way(&self, way: &[Position]) -> String {
+		let mut map = vec![vec!['.'; self.width()]; self.height()];
+		for step in way.windows(2) {
+			if let Some(direction) = Direction::between(step[0], step[1]) {
+				map[step[0].y][step[0].x] = direction.arrow();
+			}
+		}
+		if let Some(last) = way.last() {
+			map[last.y][last.x] = 'E';
+		}
+
+		map.into_iter()
+			.map(|line| {
+				let mut line: String = line.into_iter().collect();
+				line.push('\n');
+				line
+			})
+			.collect()
+	}
+}
+
+/// Length of the shortest way to the end for every position of a grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceField {
+	/// Width of the grid.
+	width: usize,
+	/// Distance of each position, `None` if the end cannot be reached.
+	distances: Vec<Option<usize>>,
+}
+
+impl DistanceField {
+	/// Get the distance at the position.
+	pub fn get(&self, pos: Position) -> Option<usize> {
+		self.distances[pos.y * self.width + pos.x]
+	}
+
+	/// Set the distance at the position.
+	fn set(&mut self, pos: Position, distance: usize) {
+		self.distances[pos.y * self.width + pos.x] = Some(distance);
+	}
+
+	/// Iterate over the rows of distances.
+	pub fn rows(&self) -> impl Iterator<Item = &[Option<usize>]> {
+		self.distances.chunks(self.width)
+	}
+}
+
+impl Display for DistanceField {
+	/// Write the distances as CSV for heatmaps, leaving unreachable positions
+	/// empty.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for row in self.rows() {
+			let cells: Vec<String> = row
+				.iter()
+				.map(|distance| distance.map(|distance| distance.to_string()).unwrap_or_default())
+				.collect();
+			writeln!(f, "{}", cells.join(","))?;
+		}
+		Ok(())
 	}
 }
 
 impl FromStr for Grid {
-	type Err = String;
+	type Err = ParseError;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		let mut width = 0;
+		let mut width = None;
 		let mut height = Vec::new();
-		let mut start = Position::default();
-		let mut end = Position::default();
+		let mut start = None;
+		let mut end = None;
 		for (y, line) in s.lines().enumerate() {
-			if width == 0 {
-				width = line.chars().count();
+			let row_width = line.chars().count();
+			if row_width == 0 {
+				return Err(ParseError::EmptyLine { line: y + 1 });
+			}
+			let expected = *width.get_or_insert(row_width);
+			if row_width != expected {
+				return Err(ParseError::InconsistentWidth {
+					line: y + 1,
+					expected,
+					found: row_width,
+				});
 			}
 
 			for (x, c) in line.chars().enumerate() {
-				if c == 'S' {
-					start = Position { x, y };
-					height.push(0);
-				} else if c == 'E' {
-					end = Position { x, y };
-					height.push(b'z' - b'a');
-				} else {
-					height.push(c as u8 - b'a');
+				let pos = Position { x, y };
+				let marker = match c {
+					'S' => Some(&mut start),
+					'E' => Some(&mut end),
+					_ => None,
+				};
+				if let Some(marker) = marker {
+					if let Some(first) = marker.replace(pos) {
+						return Err(ParseError::DuplicateMarker {
+							line: y + 1,
+							column: x + 1,
+							marker: c,
+							first_line: first.y + 1,
+							first_column: first.x + 1,
+						});
+					}
 				}
+
+				let value = match c {
+					'S' => b'a',
+					'E' => b'z',
+					'a'..='z' => c as u8,
+					_ => {
+						return Err(ParseError::InvalidHeight {
+							line: y + 1,
+							column: x + 1,
+							found: c,
+						})
+					}
+				};
+				height.push(value - b'a');
 			}
 		}
+
+		let width = width.ok_or(ParseError::Empty)?;
+		let start = start.ok_or(ParseError::MissingMarker('S'))?;
+		let end = end.ok_or(ParseError::MissingMarker('E'))?;
 		Ok(Self { width, height, start, end })
 	}
 }
+
+/// Error while parsing a heightmap. Lines and columns are 1-based.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseError {
+	#[error("The heightmap is empty")]
+	Empty,
+	#[error("Line {line}: the line is empty")]
+	EmptyLine { line: usize },
+	#[error("Line {line}: expected {expected} columns, found {found}")]
+	InconsistentWidth { line: usize, expected: usize, found: usize },
+	#[error("Line {line}, column {column}: `{found}` is not a valid height")]
+	InvalidHeight { line: usize, column: usize, found: char },
+	#[error("No `{0}` marker found")]
+	MissingMarker(char),
+	#[error(
+		"Line {line}, column {column}: duplicate `{marker}` marker, first seen at line \
+		 {first_line}, column {first_column}"
+	)]
+	DuplicateMarker {
+		line: usize,
+		column: usize,
+		marker: char,
+		first_line: usize,
+		first_column: usize,
+	},
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Example input.
+	const EXAMPLE: &str = r#"Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi"#;
+
+	#[test]
+	fn example_part1() {
+		let grid: Grid = EXAMPLE.parse().expect("parsing");
+		let way = grid.find_shortest_way_from(grid.start).expect("no way found");
+		assert_eq!(way.len() - 1, 31);
+		assert_eq!(
+			grid.render_way(&way),
+			r#">>vv<<<<
+..vvv<<^
+..vv>E^^
+..v>>>^^
+..>>>>>^
+"#
+		);
+
+		let from_end = grid.find_shortest_way_from(Position { x: 5, y: 2 });
+		assert_eq!(from_end, Some(vec![grid.end]));
+
+		let blocked: Grid = "SzE".parse().expect("parsing");
+		assert_eq!(blocked.find_shortest_way_from(blocked.start), None);
+	}
+
+	#[test]
+	fn example_part2() {
+		let grid: Grid = EXAMPLE.parse().expect("parsing");
+		assert_eq!(grid.nearest_start_with_height(0), Some((Position { x: 0, y: 4 }, 29)));
+
+		let distances = grid.distances_to_end();
+		assert_eq!(distances.get(grid.start), Some(31));
+		assert_eq!(distances.get(grid.end), Some(0));
+		assert_eq!(distances.to_string().lines().nth(2), Some("31,28,27,10,1,0,5,18"));
+
+		let blocked: Grid = "SzE".parse().expect("parsing");
+		assert_eq!(blocked.distances_to_end().to_string(), ",1,0\n");
+	}
+
+	#[test]
+	fn movement_rules() {
+		let grid: Grid = EXAMPLE.parse().expect("parsing");
+		let (cost, way) =
+			grid.find_cheapest_way_from(grid.start, &PuzzleRules).expect("no way found");
+		assert_eq!(cost, 31);
+		assert_eq!(way.len() - 1, 31);
+
+		let (cost, way) =
+			grid.find_cheapest_way_from(grid.start, &HikingRules::default()).expect("no way found");
+		assert_eq!((cost, way.len() - 1), (31, 31));
+
+		let diagonal = HikingRules { diagonal: true, ..Default::default() };
+		let (cost, way) = grid.find_cheapest_way_from(grid.start, &diagonal).expect("no way found");
+		assert_eq!(cost, way.len() - 1);
+		assert!(cost < 31);
+		assert!(way.windows(2).all(|step| step[0].steps_to(step[1], true) == 1));
+
+		let no_drops = HikingRules { max_drop: Some(0), ..Default::default() };
+		let dip: Grid = "SbcdefghijklmnopqrstuvwxyzyE".parse().expect("parsing");
+		assert!(dip.find_cheapest_way_from(dip.start, &PuzzleRules).is_some());
+		assert_eq!(dip.find_cheapest_way_from(dip.start, &no_drops), None);
+
+		let flat: Grid = "SbaaE".parse().expect("parsing");
+		let steep = HikingRules { max_climb: 25, climb_cost: 10, ..Default::default() };
+		assert_eq!(
+			flat.find_cheapest_way_from(flat.start, &steep).map(|(cost, _)| cost),
+			Some(4 + 10 + 250)
+		);
+	}
+
+	#[test]
+	fn parsing_errors() {
+		assert_eq!("".parse::<Grid>().unwrap_err(), ParseError::Empty);
+		assert_eq!(
+			"Sab\nab\nabE".parse::<Grid>().unwrap_err(),
+			ParseError::InconsistentWidth { line: 2, expected: 3, found: 2 }
+		);
+		assert_eq!("Sab\n\nabE".parse::<Grid>().unwrap_err(), ParseError::EmptyLine { line: 2 });
+		assert_eq!("\nSE".parse::<Grid>().unwrap_err(), ParseError::EmptyLine { line: 1 });
+		assert_eq!(
+			"Sab\na1E".parse::<Grid>().unwrap_err(),
+			ParseError::InvalidHeight { line: 2, column: 2, found: '1' }
+		);
+		assert_eq!(
+			"SaB\nabE".parse::<Grid>().unwrap_err(),
+			ParseError::InvalidHeight { line: 1, column: 3, found: 'B' }
+		);
+		assert_eq!("aab\nabE".parse::<Grid>().unwrap_err(), ParseError::MissingMarker('S'));
+		assert_eq!("Sab\nabc".parse::<Grid>().unwrap_err(), ParseError::MissingMarker('E'));
+		let err = "SaE\nEbS".parse::<Grid>().unwrap_err();
+		assert_eq!(
+			err,
+			ParseError::DuplicateMarker {
+				line: 2,
+				column: 1,
+				marker: 'E',
+				first_line: 1,
+				first_column: 3
+			}
+		);
+		assert_eq!(
+			err.to_string(),
+			"Line 2, column 1: duplicate `E` marker, first seen at line 1, column 3"
+		);
+	}
+}
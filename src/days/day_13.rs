@@ -26,18 +26,11 @@ pub fn run() {
 	println!();
 
 	println!("Part 2:");
-	let mut lists = parse_lists(input());
-	let divider_1 = List { items: vec![ListItem::List(List { items: vec![ListItem::Number(2)] })] };
-	let divider_2 = List { items: vec![ListItem::List(List { items: vec![ListItem::Number(6)] })] };
-	lists.push(divider_1.clone());
-	lists.push(divider_2.clone());
-	lists.sort();
-	let product_of_divider_indices: usize = lists
-		.iter()
-		.enumerate()
-		.filter(|(_, list)| **list == divider_1 || **list == divider_2)
-		.map(|(i, _)| i + 1)
-		.product();
+	let dividers = [parse_list("[[2]]"), parse_list("[[6]]")];
+	let product_of_divider_indices: usize =
+		divider_positions(ListStream::new(input()).map(|list| list.expect("parsing")), &dividers)
+			.into_iter()
+			.product();
 	println!("Product of divider indices: {product_of_divider_indices}");
 }
 
@@ -66,43 +59,109 @@ enum ListItem {
 
 impl PartialOrd for List {
 	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for List {
+	fn cmp(&self, other: &Self) -> Ordering {
 		for (item_a, item_b) in self.items.iter().zip(other.items.iter()) {
 			match item_a.cmp(item_b) {
-				Ordering::Less => return Some(Ordering::Less),
-				Ordering::Greater => return Some(Ordering::Greater),
+				Ordering::Less => return Ordering::Less,
+				Ordering::Greater => return Ordering::Greater,
 				_ => continue,
 			}
 		}
-		Some(self.items.len().cmp(&other.items.len()))
+		self.items.len().cmp(&other.items.len())
 	}
 }
 
-impl Ord for List {
-	fn cmp(&self, other: &Self) -> Ordering {
-		self.partial_cmp(other).unwrap()
+impl PartialOrd for ListItem {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
 	}
 }
 
-impl PartialOrd for ListItem {
-	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+impl Ord for ListItem {
+	fn cmp(&self, other: &Self) -> Ordering {
 		match (self, other) {
-			(Self::Number(a), Self::Number(b)) => a.partial_cmp(b),
-			(Self::List(a), Self::List(b)) => a.partial_cmp(b),
+			(Self::Number(a), Self::Number(b)) => a.cmp(b),
+			(Self::List(a), Self::List(b)) => a.cmp(b),
 			(num_a, Self::List(b)) => {
 				let a = List { items: vec![num_a.clone()] };
-				a.partial_cmp(b)
+				a.cmp(b)
 			}
 			(Self::List(a), num_b) => {
 				let b = List { items: vec![num_b.clone()] };
-				a.partial_cmp(&b)
+				a.cmp(&b)
 			}
 		}
 	}
 }
 
-impl Ord for ListItem {
-	fn cmp(&self, other: &Self) -> Ordering {
-		self.partial_cmp(other).unwrap()
+/// Compute the 1-based positions the dividers would have if they were sorted
+/// together with the given packets. Only needs a single pass over the packets,
+/// counting how many of them are smaller than each divider. Dividers are
+/// placed before packets that compare equal to them and keep their given order
+/// among dividers that compare equal to each other.
+fn divider_positions(lists: impl IntoIterator<Item = List>, dividers: &[List]) -> Vec<usize> {
+	// Dividers are sorted into the packets as well, so they count against each
+	// other. Ties between dividers are broken by their index.
+	let mut positions: Vec<usize> = dividers
+		.iter()
+		.enumerate()
+		.map(|(i, divider)| {
+			let smaller = dividers.iter().filter(|other| *other < divider).count();
+			let equal_before =
+				dividers[..i].iter().filter(|&other| other.cmp(divider) == Ordering::Equal).count();
+			1 + smaller + equal_before
+		})
+		.collect();
+	for list in lists {
+		for (position, divider) in positions.iter_mut().zip(dividers) {
+			if list < *divider {
+				*position += 1;
+			}
+		}
+	}
+	positions
+}
+
+/// Iterator over the packets of the input, parsing one list at a time without
+/// collecting them.
+struct ListStream<'a> {
+	/// Remaining unparsed input.
+	input: &'a str,
+	/// Full input for error reporting.
+	full_input: &'a str,
+}
+
+impl<'a> ListStream<'a> {
+	/// Create a new [ListStream] over the given input.
+	pub fn new(input: &'a str) -> Self {
+		Self { input, full_input: input }
+	}
+}
+
+impl Iterator for ListStream<'_> {
+	type Item = Result<List, String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.input = self.input.trim_start();
+		if self.input.is_empty() {
+			return None;
+		}
+
+		match nom_list(self.input).finish() {
+			Ok((rest, list)) => {
+				self.input = rest;
+				Some(Ok(list))
+			}
+			Err(err) => {
+				self.input = "";
+				Some(Err(convert_error(self.full_input, err)))
+			}
+		}
 	}
 }
 
@@ -118,8 +177,8 @@ fn parse_pairs(input: &str) -> Vec<Pair> {
 	pairs
 }
 
-fn parse_lists(input: &str) -> Vec<List> {
-	let (_rest, lists) = nom_lists(input)
+fn parse_list(input: &str) -> List {
+	let (_rest, list) = nom_list(input)
 		.finish()
 		.map_err(|err| convert_error(input, err))
 		.map_err(|err| {
@@ -127,37 +186,85 @@ fn parse_lists(input: &str) -> Vec<List> {
 			err
 		})
 		.expect("parsing");
-	lists
+	list
 }
 
-fn nom_pairs(input: &str) -> MyIResult<Vec<Pair>> {
+fn nom_pairs(input: &str) -> MyIResult<'_, Vec<Pair>> {
 	separated_list1(multispace1, nom_pair)(input)
 }
 
-fn nom_pair(input: &str) -> MyIResult<Pair> {
+fn nom_pair(input: &str) -> MyIResult<'_, Pair> {
 	let (input, a) = nom_list(input)?;
 	let (input, _) = multispace1(input)?;
 	let (output, b) = nom_list(input)?;
 	Ok((output, Pair { a, b }))
 }
 
-fn nom_lists(input: &str) -> MyIResult<Vec<List>> {
-	separated_list1(multispace1, nom_list)(input)
-}
-
-fn nom_list(input: &str) -> MyIResult<List> {
+fn nom_list(input: &str) -> MyIResult<'_, List> {
 	let (input, _) = nom_char('[')(input)?;
 	let (input, items) = separated_list0(nom_char(','), nom_list_item)(input)?;
 	let (output, _) = nom_char(']')(input)?;
 	Ok((output, List { items }))
 }
 
-fn nom_list_item(input: &str) -> MyIResult<ListItem> {
+fn nom_list_item(input: &str) -> MyIResult<'_, ListItem> {
 	let number = map(nom_number, ListItem::Number);
 	let list = map(nom_list, ListItem::List);
 	alt((number, list))(input)
 }
 
-fn nom_number<N: FromStr>(input: &str) -> MyIResult<N> {
+fn nom_number<N: FromStr>(input: &str) -> MyIResult<'_, N> {
 	map_res(digit1, |digit: &str| digit.parse())(input)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Example input.
+	const EXAMPLE: &str = r#"[1,1,3,1,1]
+[1,1,5,1,1]
+
+[[1],[2,3,4]]
+[[1],4]
+
+[9]
+[[8,7,6]]
+
+[[4,4],4,4]
+[[4,4],4,4,4]
+
+[7,7,7,7]
+[7,7,7]
+
+[]
+[3]
+
+[[[]]]
+[[]]
+
+[1,[2,[3,[4,[5,6,7]]]],8,9]
+[1,[2,[3,[4,[5,6,0]]]],8,9]"#;
+
+	#[test]
+	fn example_part2() {
+		let dividers = [parse_list("[[2]]"), parse_list("[[6]]")];
+		let lists = ListStream::new(EXAMPLE).map(|list| list.expect("parsing"));
+		assert_eq!(divider_positions(lists, &dividers), vec![10, 14]);
+	}
+
+	#[test]
+	fn custom_dividers() {
+		let dividers = [parse_list("[[6]]"), parse_list("[]"), parse_list("[[2]]")];
+		let lists = ListStream::new(EXAMPLE).map(|list| list.expect("parsing"));
+		assert_eq!(divider_positions(lists, &dividers), vec![15, 1, 11]);
+
+		let dividers = [parse_list("[[2]]"), parse_list("[[6]]"), parse_list("[[2]]")];
+		let lists = ListStream::new(EXAMPLE).map(|list| list.expect("parsing"));
+		assert_eq!(divider_positions(lists, &dividers), vec![10, 15, 11]);
+
+		// Equal by the packet ordering, although structurally different.
+		let dividers = [parse_list("[[2]]"), parse_list("[2]")];
+		assert_eq!(divider_positions([], &dividers), vec![1, 2]);
+	}
+}
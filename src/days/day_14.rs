@@ -1,91 +1,387 @@
 //! Day 14.
 
-use std::{collections::HashMap, str::FromStr};
+use std::{
+	fmt::{self, Display},
+	str::FromStr,
+};
+
+use thiserror::Error;
 
 fn input() -> &'static str {
 	include_str!("day_14.txt")
 }
 
-pub fn run() {
-	let mut map_1: Map = input().parse().expect("parsing");
-	let mut map_2 = map_1.clone();
+pub fn run(animate: bool, frame_interval: usize) {
+	let scan: Scan = input().parse().expect("parsing");
 
 	println!("Part 1:");
-	let sand_capacity = map_1.count_sand_capacity(false);
+	let mut map = Map::new(&scan, CaveConfig::default()).expect("creating cave");
+	let sand_capacity: usize = if animate {
+		print_frames(&mut map, frame_interval).iter().sum()
+	} else {
+		map.count_sand_capacity()
+	};
 	println!("Sand capacity: {sand_capacity}");
 	println!();
 
 	println!("Part 2:");
-	let sand_capacity = map_2.count_sand_capacity(true);
+	let mut map = Map::new(&scan, CaveConfig { floor: Some(2), ..Default::default() })
+		.expect("creating cave");
+	let sand_capacity = if animate {
+		print_frames(&mut map, frame_interval).iter().sum()
+	} else {
+		map.sand_capacity_with_floor().expect("the cave has a floor")
+	};
 	println!("Sand capacity: {sand_capacity}");
 }
 
+/// Pour sand until every source is exhausted, printing the cave every
+/// `interval` grains and at the end. Returns the number of grains that came
+/// to rest per source.
+fn print_frames(map: &mut Map, interval: usize) -> Vec<usize> {
+	let print_frame = |map: &Map, sands: usize| {
+		println!("== {sands} grains ==");
+		println!();
+		println!("{}", map.render());
+	};
+
+	let mut counts = vec![0; map.config.sources.len()];
+	let mut last_frame = 0;
+	println!("{}", map.render());
+	while map.pour_round(&mut counts) {
+		let sands: usize = counts.iter().sum();
+		if sands / interval > last_frame / interval {
+			print_frame(map, sands);
+			last_frame = sands;
+		}
+	}
+	let sands = counts.iter().sum();
+	if sands != last_frame {
+		print_frame(map, sands);
+	}
+	counts
+}
+
+/// Position the sand is poured in from in the puzzle.
+const SOURCE: Position = Position { x: 500, y: 0 };
+
+/// Largest absolute coordinate accepted in a scan or cave configuration, which
+/// keeps all coordinate computations free of overflows.
+const MAX_COORDINATE: isize = 1 << 24;
+
+/// Largest number of cells of a map, as every cell of the cave is stored.
+const MAX_CELLS: usize = 1 << 26;
+
+/// Configuration of the cave around the scanned rocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaveConfig {
+	/// Positions the sand is poured in from. Each source emits one grain per
+	/// round, in order.
+	pub sources: Vec<Position>,
+	/// Offset of the floor below the lowest rock, which must be positive.
+	/// Without floor, sand falling below the lowest rock is lost to the abyss.
+	pub floor: Option<isize>,
+	/// Columns of the left and right walls, which reach from the top to the
+	/// bottom of the cave.
+	pub walls: Option<(isize, isize)>,
+}
+
+impl Default for CaveConfig {
+	fn default() -> Self {
+		Self { sources: vec![SOURCE], floor: None, walls: None }
+	}
+}
+
 impl Map {
-	fn possible_direction(&self, from_pos: Position) -> Option<Position> {
-		let down = Position { x: from_pos.x, y: from_pos.y + 1 };
-		if self.blocks.get(&down).copied().unwrap_or_default() == Block::Air {
-			return Some(down);
+	/// Create the map of a cave with the scanned rocks and the given
+	/// configuration. Fails if the configuration exceeds the coordinate limit,
+	/// the floor is not below the lowest rock or the cave has more than
+	/// [MAX_CELLS] cells.
+	pub fn new(scan: &Scan, config: CaveConfig) -> Result<Self, CaveError> {
+		let mut configured = config
+			.sources
+			.iter()
+			.flat_map(|source| [source.x, source.y])
+			.chain(config.floor)
+			.chain(config.walls.into_iter().flat_map(|(left, right)| [left, right]));
+		if let Some(value) = configured.find(|value| value.abs() > MAX_COORDINATE) {
+			return Err(CaveError::OutOfRange(value));
+		}
+		if let Some(offset) = config.floor.filter(|offset| *offset <= 0) {
+			return Err(CaveError::FloorAboveRocks(offset));
 		}
 
-		let left = Position { x: from_pos.x - 1, y: from_pos.y + 1 };
-		if self.blocks.get(&left).copied().unwrap_or_default() == Block::Air {
-			return Some(left);
+		let used = || scan.rocks.iter().chain(&config.sources);
+		let min_y = used().map(|pos| pos.y).min().unwrap_or(0);
+		let lowest_rock = scan.rocks.iter().map(|pos| pos.y).max().unwrap_or(min_y);
+		let floor_y = config.floor.map(|offset| lowest_rock + offset);
+		// Lowest row sand can come to rest in.
+		let bottom = floor_y.map_or(lowest_rock, |floor_y| floor_y - 1);
+
+		// Sand spreads at most one column per row, so it stays within a triangle
+		// below each source.
+		let mut min_x = used().map(|pos| pos.x).min().unwrap_or(0);
+		let mut max_x = used().map(|pos| pos.x).max().unwrap_or(0);
+		for source in &config.sources {
+			let spread = (bottom - source.y).max(0) + 1;
+			min_x = min_x.min(source.x - spread);
+			max_x = max_x.max(source.x + spread);
+		}
+		if let Some((left, right)) = config.walls {
+			min_x = min_x.min(left);
+			max_x = max_x.max(right);
 		}
 
-		let right = Position { x: from_pos.x + 1, y: from_pos.y + 1 };
-		if self.blocks.get(&right).copied().unwrap_or_default() == Block::Air {
-			return Some(right);
+		let width = usize::try_from(max_x - min_x + 1).expect("bounds are ordered");
+		let height = usize::try_from(floor_y.unwrap_or(lowest_rock).max(min_y) - min_y + 1)
+			.expect("bounds are ordered");
+		if width.saturating_mul(height) > MAX_CELLS {
+			return Err(CaveError::TooLarge { width, height });
+		}
+
+		let mut map = Self {
+			blocks: vec![Block::Air; width * height],
+			min_x,
+			min_y,
+			width,
+			lowest_rock,
+			paths: vec![Vec::new(); config.sources.len()],
+			exhausted: vec![false; config.sources.len()],
+			config,
+		};
+		for &rock in &scan.rocks {
+			map.set(rock, Block::Rock);
+		}
+		if let Some(floor_y) = floor_y {
+			for x in min_x..=max_x {
+				map.set(Position { x, y: floor_y }, Block::Rock);
+			}
 		}
+		if let Some((left, right)) = map.config.walls {
+			for y in min_y..min_y + map.height() as isize {
+				map.set(Position { x: left, y }, Block::Rock);
+				map.set(Position { x: right, y }, Block::Rock);
+			}
+		}
+		Ok(map)
+	}
+
+	/// Index of the position in the blocks, `None` if it is not on the map.
+	fn index(&self, pos: Position) -> Option<usize> {
+		let x = usize::try_from(pos.x - self.min_x).ok().filter(|x| *x < self.width)?;
+		let y = usize::try_from(pos.y - self.min_y).ok().filter(|y| *y < self.height())?;
+		Some(y * self.width + x)
+	}
+
+	/// Get the block at the position. Everything outside the map is air.
+	fn get(&self, pos: Position) -> Block {
+		self.index(pos).map_or(Block::Air, |index| self.blocks[index])
+	}
 
-		None
+	/// Set the block at the position. Positions outside the map are ignored.
+	fn set(&mut self, pos: Position, block: Block) {
+		if let Some(index) = self.index(pos) {
+			self.blocks[index] = block;
+		}
+	}
+
+	/// Number of rows on the map, including the floor.
+	fn height(&self) -> usize {
+		self.blocks.len() / self.width
+	}
+
+	fn possible_direction(&self, from_pos: Position) -> Option<Position> {
+		let down = Position { x: from_pos.x, y: from_pos.y + 1 };
+		let left = Position { x: from_pos.x - 1, y: from_pos.y + 1 };
+		let right = Position { x: from_pos.x + 1, y: from_pos.y + 1 };
+		[down, left, right].into_iter().find(|pos| self.get(*pos) == Block::Air)
 	}
 
-	/// Spawn a new sand and let is fall down step by step. Possible to run with
-	/// or without floor. Return whether it came to a stop.
-	pub fn new_sand_falls(&mut self, floor: bool) -> bool {
-		let mut pos = Position { x: 500, y: 0 };
-		if self.blocks.get(&pos).copied().unwrap_or_default() != Block::Air {
+	/// Spawn a new sand at the source with the given index and let it fall
+	/// down step by step. Return whether it came to a stop. Sand leaving the
+	/// map falls into the abyss. Once a source is blocked or lost a grain to
+	/// the abyss, it is exhausted and this always returns `false`.
+	///
+	/// The path of the source's previous grain is kept, so a new grain resumes
+	/// from the last position before the previous one came to rest instead of
+	/// falling all the way from the source again. Positions filled by other
+	/// sources in the meantime are dropped from the path.
+	pub fn new_sand_falls(&mut self, source: usize) -> bool {
+		if self.exhausted[source] {
 			return false;
 		}
 
-		while let Some(next) = self.possible_direction(pos) {
-			pos = next;
-			if pos.y > self.lowest_rock {
-				if floor {
+		let mut path = std::mem::take(&mut self.paths[source]);
+		while path.last().is_some_and(|pos| self.get(*pos) != Block::Air) {
+			path.pop();
+		}
+		let start = self.config.sources[source];
+		if path.is_empty() && self.get(start) == Block::Air {
+			path.push(start);
+		}
+
+		let mut settled = false;
+		while let Some(&pos) = path.last() {
+			match self.possible_direction(pos) {
+				Some(next) if self.index(next).is_none() => break,
+				Some(next) => path.push(next),
+				None => {
+					self.set(pos, Block::Sand);
+					path.pop();
+					settled = true;
 					break;
-				} else {
-					return false;
 				}
 			}
 		}
-		self.blocks.insert(pos, Block::Sand);
-		true
+
+		self.exhausted[source] = !settled;
+		self.paths[source] = path;
+		settled
+	}
+
+	/// Let every source that is not exhausted emit one grain of sand and add
+	/// the grains that came to rest to the per-source counts. Return whether
+	/// any grain came to rest.
+	pub fn pour_round(&mut self, counts: &mut [usize]) -> bool {
+		let mut any = false;
+		for (source, count) in counts.iter_mut().enumerate() {
+			if self.new_sand_falls(source) {
+				*count += 1;
+				any = true;
+			}
+		}
+		any
+	}
+
+	/// Pour sand until every source is exhausted and return the number of
+	/// grains that came to rest per source.
+	pub fn pour(&mut self) -> Vec<usize> {
+		let mut counts = vec![0; self.config.sources.len()];
+		while self.pour_round(&mut counts) {}
+		counts
 	}
 
 	/// Count the number of sand blocks dropped until they land in abyss or
-	/// blocks the source.
-	pub fn count_sand_capacity(&mut self, floor: bool) -> usize {
-		let mut sands = 0;
-		while self.new_sand_falls(floor) {
-			sands += 1;
+	/// block the sources.
+	pub fn count_sand_capacity(&mut self) -> usize {
+		self.pour().iter().sum()
+	}
+
+	/// Render the cave in the puzzle's notation over the area of all rocks,
+	/// sand and sources. The floor is drawn below this area.
+	pub fn render(&self) -> String {
+		let floor_y = self.config.floor.map(|offset| self.lowest_rock + offset);
+		let used = (0..self.blocks.len())
+			.filter(|&i| self.blocks[i] != Block::Air)
+			.map(|i| self.position(i))
+			.filter(|pos| Some(pos.y) != floor_y)
+			.chain(self.config.sources.iter().copied());
+		let (mut min_x, mut max_x) = (isize::MAX, isize::MIN);
+		let (mut min_y, mut max_y) = (isize::MAX, isize::MIN);
+		for pos in used {
+			min_x = min_x.min(pos.x);
+			max_x = max_x.max(pos.x);
+			min_y = min_y.min(pos.y);
+			max_y = max_y.max(pos.y);
 		}
-		sands
+		if let Some(floor_y) = floor_y {
+			max_y = floor_y;
+		}
+
+		let mut output = String::new();
+		for y in min_y..=max_y {
+			output.extend((min_x..=max_x).map(|x| {
+				let pos = Position { x, y };
+				match self.get(pos) {
+					_ if Some(y) == floor_y => '#',
+					Block::Rock => '#',
+					Block::Sand => 'o',
+					Block::Air if self.config.sources.contains(&pos) => '+',
+					Block::Air => '.',
+				}
+			}));
+			output.push('\n');
+		}
+		output
+	}
+
+	/// Position of the block at the given index.
+	fn position(&self, index: usize) -> Position {
+		let x = self.min_x + (index % self.width) as isize;
+		let y = self.min_y + (index / self.width) as isize;
+		Position { x, y }
+	}
+
+	/// Count the number of sand blocks dropped until they block all sources
+	/// without simulating single grains. Only possible with a floor, where sand
+	/// comes to rest at every position it can reach, which is every free
+	/// position below a reachable position in the row above or a source.
+	/// Returns `None` for caves without floor.
+	pub fn sand_capacity_with_floor(&self) -> Option<usize> {
+		let floor_y = self.lowest_rock + self.config.floor?;
+
+		let mut reachable = vec![false; self.width];
+		let mut count = 0;
+		for (row_index, row) in self.blocks.chunks(self.width).enumerate() {
+			let y = self.min_y + row_index as isize;
+			if y >= floor_y {
+				break;
+			}
+
+			let previous = reachable.clone();
+			for (i, cell) in reachable.iter_mut().enumerate() {
+				let pos = Position { x: self.min_x + i as isize, y };
+				let above = i.saturating_sub(1)..=(i + 1).min(self.width - 1);
+				let fed = previous[above].contains(&true) || self.config.sources.contains(&pos);
+				*cell = row[i] == Block::Air && fed;
+				count += usize::from(*cell);
+			}
+		}
+		Some(count)
 	}
 }
 
 /// The map of the cave.
 #[derive(Debug, Clone)]
-struct Map {
-	/// The map information, i.e. where is which block.
-	blocks: HashMap<Position, Block>,
+pub struct Map {
+	/// The map information, i.e. where is which block, stored row by row.
+	blocks: Vec<Block>,
+	/// Smallest x coordinate on the map.
+	min_x: isize,
+	/// Smallest y coordinate on the map.
+	min_y: isize,
+	/// Number of columns on the map.
+	width: usize,
 	/// Cache of the lowest rock y.
-	lowest_rock: usize,
+	lowest_rock: isize,
+	/// Sources, floor and walls of the cave.
+	config: CaveConfig,
+	/// Path of the last grain of sand of each source, up to the position it
+	/// came to rest.
+	paths: Vec<Vec<Position>>,
+	/// Whether each source is blocked or lost sand to the abyss.
+	exhausted: Vec<bool>,
+}
+
+/// Rocks of the cave as found by the scan.
+#[derive(Debug, Clone, Default)]
+pub struct Scan {
+	/// Positions of all rocks.
+	rocks: Vec<Position>,
 }
 
+/// A position in the cave. The cave extends to both sides without bounds, so
+/// coordinates may also be negative.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Position {
-	x: usize,
-	y: usize,
+pub struct Position {
+	pub x: isize,
+	pub y: isize,
+}
+
+impl Display for Position {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{},{}", self.x, self.y)
+	}
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -96,49 +392,89 @@ enum Block {
 	Sand,
 }
 
+/// Error while parsing the scan of the rocks. Lines and columns are 1-based,
+/// columns point to the start of the offending position.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseError {
+	#[error("Line {line}, column {column}: `{position}` is not a valid position")]
+	InvalidPosition { line: usize, column: usize, position: String },
+	#[error(
+		"Line {line}, column {column}: `{position}` exceeds the coordinate limit of {MAX_COORDINATE}"
+	)]
+	OutOfRange { line: usize, column: usize, position: Position },
+	#[error("Line {line}, column {column}: diagonal line from `{start}` to `{end}`")]
+	DiagonalLine { line: usize, column: usize, start: Position, end: Position },
+	#[error("Line {line}, column {column}: zero-length line at `{position}`")]
+	ZeroLengthLine { line: usize, column: usize, position: Position },
+	#[error("Line {line}: a rock path needs at least two points")]
+	SinglePoint { line: usize },
+}
+
+/// Error while creating the map of a cave.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CaveError {
+	#[error("`{0}` in the cave configuration exceeds the coordinate limit of {MAX_COORDINATE}")]
+	OutOfRange(isize),
+	#[error("The floor offset `{0}` does not put the floor below the lowest rock")]
+	FloorAboveRocks(isize),
+	#[error("The cave spans {width}x{height} cells, more than the limit of {MAX_CELLS}")]
+	TooLarge { width: usize, height: usize },
+}
+
+/// Add the rocks of a straight line between two points to the list.
 fn make_rock_line(
-	blocks: &mut HashMap<Position, Block>,
+	rocks: &mut Vec<Position>,
 	start: Position,
 	end: Position,
-) -> Result<(), String> {
-	if start.x == end.x {
+	line: usize,
+	column: usize,
+) -> Result<(), ParseError> {
+	if start == end {
+		return Err(ParseError::ZeroLengthLine { line, column, position: start });
+	} else if start.x == end.x {
 		let from = start.y.min(end.y);
 		let to = start.y.max(end.y);
-		for y in from..=to {
-			let pos = Position { x: start.x, y };
-			blocks.insert(pos, Block::Rock);
-		}
+		rocks.extend((from..=to).map(|y| Position { x: start.x, y }));
 	} else if start.y == end.y {
 		let from = start.x.min(end.x);
 		let to = start.x.max(end.x);
-		for x in from..=to {
-			let pos = Position { x, y: start.y };
-			blocks.insert(pos, Block::Rock);
-		}
+		rocks.extend((from..=to).map(|x| Position { x, y: start.y }));
 	} else {
-		return Err("Diagonal lines in rock definitions!".to_owned());
+		return Err(ParseError::DiagonalLine { line, column, start, end });
 	}
 	Ok(())
 }
 
-impl FromStr for Map {
-	type Err = String;
+impl FromStr for Scan {
+	type Err = ParseError;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		let mut blocks = HashMap::new();
-		let mut lowest_rock = 0;
-		for line in s.lines() {
-			let mut positions = line.split(" -> ").peekable();
-			while let Some(start_str) = positions.next() {
-				if let Some(end_str) = positions.peek() {
-					let start = start_str.parse::<Position>()?;
-					let end = end_str.parse::<Position>()?;
-					make_rock_line(&mut blocks, start, end)?;
-					lowest_rock = lowest_rock.max(start.y).max(end.y);
+		const SEPARATOR: &str = " -> ";
+
+		let mut rocks = Vec::new();
+		for (line, path) in s.lines().enumerate().map(|(i, path)| (i + 1, path)) {
+			let mut previous: Option<(Position, usize)> = None;
+			let mut column = 1;
+			for point in path.split(SEPARATOR) {
+				let position = point.parse::<Position>().map_err(|_| {
+					ParseError::InvalidPosition { line, column, position: point.to_owned() }
+				})?;
+				if position.x.abs() > MAX_COORDINATE || position.y.abs() > MAX_COORDINATE {
+					return Err(ParseError::OutOfRange { line, column, position });
+				}
+
+				if let Some((start, start_column)) = previous {
+					make_rock_line(&mut rocks, start, position, line, start_column)?;
 				}
+				previous = Some((position, column));
+				column += point.chars().count() + SEPARATOR.len();
+			}
+
+			if !path.contains(SEPARATOR) {
+				return Err(ParseError::SinglePoint { line });
 			}
 		}
-		Ok(Self { blocks, lowest_rock })
+		Ok(Self { rocks })
 	}
 }
 
@@ -153,3 +489,194 @@ impl FromStr for Position {
 		Ok(Self { x, y })
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Example input.
+	const EXAMPLE: &str = r#"498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9"#;
+
+	#[test]
+	fn example() {
+		let scan: Scan = EXAMPLE.parse().expect("parsing");
+		let mut map = Map::new(&scan, CaveConfig::default()).expect("creating cave");
+		assert_eq!(map.sand_capacity_with_floor(), None);
+		assert_eq!(map.count_sand_capacity(), 24);
+
+		let floor = CaveConfig { floor: Some(2), ..Default::default() };
+		let mut map = Map::new(&scan, floor).expect("creating cave");
+		assert_eq!(map.sand_capacity_with_floor(), Some(93));
+		assert_eq!(map.count_sand_capacity(), 93);
+	}
+
+	#[test]
+	fn rendering() {
+		let scan: Scan = EXAMPLE.parse().expect("parsing");
+		let mut map = Map::new(&scan, CaveConfig::default()).expect("creating cave");
+		assert_eq!(
+			map.render(),
+			r#"......+...
+..........
+..........
+..........
+....#...##
+....#...#.
+..###...#.
+........#.
+........#.
+#########.
+"#
+		);
+
+		map.count_sand_capacity();
+		assert_eq!(
+			map.render(),
+			r#"......+...
+..........
+......o...
+.....ooo..
+....#ooo##
+...o#ooo#.
+..###ooo#.
+....oooo#.
+.o.ooooo#.
+#########.
+"#
+		);
+
+		let mut map = Map::new(&scan, CaveConfig { floor: Some(2), ..Default::default() })
+			.expect("creating cave");
+		map.count_sand_capacity();
+		assert_eq!(
+			map.render(),
+			r#"..........o..........
+.........ooo.........
+........ooooo........
+.......ooooooo.......
+......oo#ooo##o......
+.....ooo#ooo#ooo.....
+....oo###ooo#oooo....
+...oooo.oooo#ooooo...
+..oooooooooo#oooooo..
+.ooo#########ooooooo.
+ooooo.......ooooooooo
+#####################
+"#
+		);
+	}
+
+	#[test]
+	fn cave_configuration() {
+		let scan: Scan = EXAMPLE.parse().expect("parsing");
+
+		let config = CaveConfig {
+			sources: vec![Position { x: 500, y: 0 }, Position { x: 497, y: 0 }],
+			floor: Some(3),
+			walls: Some((493, 504)),
+		};
+		let mut map = Map::new(&scan, config).expect("creating cave");
+		let capacity = map.sand_capacity_with_floor();
+		let counts = map.pour();
+		assert_eq!(Some(counts.iter().sum()), capacity);
+		assert_eq!(counts, [30, 30]);
+		assert_eq!(
+			map.render(),
+			r#"#...o..o...#
+#..oooooo..#
+#.oooooooo.#
+#oooooooooo#
+#oooo#ooo###
+#oooo#ooo#.#
+#oo###ooo#.#
+#ooo.oooo#.#
+#oooooooo#.#
+##########.#
+#..........#
+#..........#
+############
+"#
+		);
+
+		let config = CaveConfig { sources: vec![Position { x: 499, y: 5 }], ..Default::default() };
+		let mut map = Map::new(&scan, config).expect("creating cave");
+		assert_eq!(map.pour(), [12]);
+		assert!(!map.new_sand_falls(0));
+
+		for floor in [0, -2] {
+			let config = CaveConfig { floor: Some(floor), ..Default::default() };
+			assert_eq!(Map::new(&scan, config).unwrap_err(), CaveError::FloorAboveRocks(floor));
+		}
+	}
+
+	#[test]
+	fn parsing_errors() {
+		let parse = |s: &str| s.parse::<Scan>().unwrap_err();
+		assert_eq!(parse("1,2"), ParseError::SinglePoint { line: 1 });
+		assert_eq!(
+			parse("1,2 -> 1,4\n3,3 -> 3,x"),
+			ParseError::InvalidPosition { line: 2, column: 8, position: "3,x".to_owned() }
+		);
+		assert_eq!(
+			parse("1,2 -> 1,4 -> 1,4"),
+			ParseError::ZeroLengthLine { line: 1, column: 8, position: Position { x: 1, y: 4 } }
+		);
+		let err = parse("10,2 -> 12,4");
+		assert_eq!(
+			err,
+			ParseError::DiagonalLine {
+				line: 1,
+				column: 1,
+				start: Position { x: 10, y: 2 },
+				end: Position { x: 12, y: 4 }
+			}
+		);
+		assert_eq!(err.to_string(), "Line 1, column 1: diagonal line from `10,2` to `12,4`");
+		assert!(matches!(
+			parse("1,2 -> 99999999999,2"),
+			ParseError::OutOfRange { line: 1, column: 8, .. }
+		));
+		assert!(matches!(
+			parse("1,2 -> 999999999999999999999,2"),
+			ParseError::InvalidPosition { line: 1, column: 8, .. }
+		));
+	}
+
+	#[test]
+	fn left_edge() {
+		let scan: Scan = "0,3 -> 2,3\n-1,-2 -> -1,-1".parse().expect("parsing");
+		let config = CaveConfig { sources: vec![Position { x: 1, y: 0 }], ..Default::default() };
+		let mut map = Map::new(&scan, config.clone()).expect("creating cave");
+		assert_eq!(map.count_sand_capacity(), 1);
+		assert_eq!(map.render(), "#...\n#...\n..+.\n....\n..o.\n.###\n");
+
+		let mut map =
+			Map::new(&scan, CaveConfig { floor: Some(2), ..config }).expect("creating cave");
+		assert_eq!(map.sand_capacity_with_floor(), Some(21));
+		assert_eq!(map.count_sand_capacity(), 21);
+		assert_eq!(
+			map.render(),
+			r#"..#......
+..#......
+....o....
+...ooo...
+..ooooo..
+.oo###oo.
+oooo.oooo
+#########
+"#
+		);
+	}
+
+	#[test]
+	fn cave_size_limits() {
+		let scan: Scan = "0,16000000 -> 1,16000000".parse().expect("parsing");
+		let err = Map::new(&scan, CaveConfig::default()).unwrap_err();
+		assert!(matches!(err, CaveError::TooLarge { .. }));
+
+		let scan: Scan = EXAMPLE.parse().expect("parsing");
+		let config = CaveConfig { floor: Some(isize::MAX), ..Default::default() };
+		assert_eq!(Map::new(&scan, config).unwrap_err(), CaveError::OutOfRange(isize::MAX));
+	}
+}
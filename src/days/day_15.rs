@@ -1,8 +1,9 @@
 //! Day 15.
 
-use std::{ops::RangeInclusive, str::FromStr};
+use std::{collections::HashSet, ops::RangeInclusive, str::FromStr};
 
 use lazy_regex::regex_captures;
+use rayon::prelude::*;
 
 fn input() -> &'static str {
 	include_str!("day_15.txt")
@@ -27,70 +28,60 @@ pub fn run() {
 struct Sensors(Vec<Sensor>);
 
 impl Sensors {
-	/// Return the range of where the sensors can reach.
-	fn x_range(&self) -> RangeInclusive<isize> {
-		let min = self
-			.0
-			.iter()
-			.map(|sensor| sensor.position.x - sensor.distance_to_beacon() as isize)
-			.min()
-			.expect("There must be at least one sensor..");
-		let max = self
-			.0
-			.iter()
-			.map(|sensor| sensor.position.x + sensor.distance_to_beacon() as isize)
-			.max()
-			.expect("There must be at least one sensor..");
-		min..=max
-	}
-
-	/// Return whether the sensors exclude this position.
-	fn exclude(&self, pos: Position) -> bool {
-		self.0.iter().any(|sensor| sensor.distance_to(pos) <= sensor.distance_to_beacon())
-	}
-
-	/// Return whether at the position is a known beacon.
-	fn is_beacon(&self, pos: Position) -> bool {
-		self.0.iter().any(|sensor| pos == sensor.closest_beacon)
+	/// Return the ranges of x positions excluded by the sensors in the row,
+	/// sorted and merged, so they neither overlap nor touch.
+	pub fn row_coverage(&self, y: isize) -> Vec<RangeInclusive<isize>> {
+		let mut ranges: Vec<_> =
+			self.0.iter().filter_map(|sensor| sensor.covered_range(y)).collect();
+		ranges.sort_unstable_by_key(|range| *range.start());
+
+		let mut merged: Vec<RangeInclusive<isize>> = Vec::with_capacity(ranges.len());
+		for range in ranges {
+			match merged.last_mut() {
+				Some(last) if *range.start() <= *last.end() + 1 => {
+					if range.end() > last.end() {
+						*last = *last.start()..=*range.end();
+					}
+				}
+				_ => merged.push(range),
+			}
+		}
+		merged
 	}
 
 	pub fn excluded_position_count(&self, height: isize) -> usize {
-		self.x_range()
-			.map(|x| Position { x, y: height })
-			.filter(|pos| !self.is_beacon(*pos))
-			.filter(|pos| self.exclude(*pos))
-			.count()
+		let coverage = self.row_coverage(height);
+		let covered: usize =
+			coverage.iter().map(|range| range.start().abs_diff(*range.end()) + 1).sum();
+		// Known beacons are always within the range of their sensor.
+		let beacons: HashSet<isize> = self
+			.0
+			.iter()
+			.filter(|sensor| sensor.closest_beacon.y == height)
+			.map(|sensor| sensor.closest_beacon.x)
+			.collect();
+		covered - beacons.len()
 	}
 
-	pub fn find_available_position_in(&self, area: RangeInclusive<isize>) -> Option<Position> {
-		for sensor in &self.0 {
-			for i in 0..=sensor.distance_to_beacon() as isize {
-				let top = Position {
-					x: sensor.position.x + i,
-					y: sensor.position.y - sensor.distance_to_beacon() as isize - 1 + i,
-				};
-				let right = Position {
-					x: sensor.position.x + sensor.distance_to_beacon() as isize + 1 - i,
-					y: sensor.position.y + i,
-				};
-				let bottom = Position {
-					x: sensor.position.x - i,
-					y: sensor.position.y + sensor.distance_to_beacon() as isize + 1 - i,
-				};
-				let left = Position {
-					x: sensor.position.x - sensor.distance_to_beacon() as isize - 1 + i,
-					y: sensor.position.y - i,
-				};
-				let all = [top, right, bottom, left];
-
-				for pos in all {
-					if !self.exclude(pos) && area.contains(&pos.x) && area.contains(&pos.y) {
-						return Some(pos);
-					}
-				}
+	/// Return the first x position within the range that no sensor excludes in
+	/// the row.
+	fn find_gap_in_row(&self, y: isize, xs: &RangeInclusive<isize>) -> Option<isize> {
+		let mut x = *xs.start();
+		for range in self.row_coverage(y) {
+			if x < *range.start() {
+				break;
 			}
+			x = x.max(*range.end() + 1);
 		}
-		None
+		xs.contains(&x).then_some(x)
+	}
+
+	/// Find a position in the square area that no sensor excludes, scanning the
+	/// rows in parallel. Returns the first such position by row.
+	pub fn find_available_position_in(&self, area: RangeInclusive<isize>) -> Option<Position> {
+		area.clone()
+			.into_par_iter()
+			.find_map_first(|y| self.find_gap_in_row(y, &area).map(|x| Position { x, y }))
 	}
 }
 
@@ -123,6 +114,13 @@ impl Sensor {
 	fn distance_to_beacon(&self) -> usize {
 		self.distance_to(self.closest_beacon)
 	}
+
+	/// Return the range of x positions the sensor excludes in the row, if any.
+	fn covered_range(&self, y: isize) -> Option<RangeInclusive<isize>> {
+		let dy = self.position.y.abs_diff(y);
+		let reach = self.distance_to_beacon().checked_sub(dy)? as isize;
+		Some(self.position.x - reach..=self.position.x + reach)
+	}
 }
 
 impl FromStr for Sensor {
@@ -179,4 +177,15 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3"#
 		let sensors: Sensors = example_input().parse().expect("parsing");
 		assert_eq!(sensors.find_available_position_in(0..=20), Some(Position { x: 14, y: 11 }))
 	}
+
+	#[test]
+	fn row_coverage() {
+		let sensors: Sensors = example_input().parse().expect("parsing");
+		assert_eq!(sensors.row_coverage(10), [-2..=24]);
+		assert_eq!(sensors.row_coverage(11), [-3..=13, 15..=25]);
+		assert_eq!(sensors.excluded_position_count(11), 28);
+		assert_eq!(sensors.excluded_position_count(-100), 0);
+		assert_eq!(sensors.find_available_position_in(12..=20), None);
+		assert_eq!(sensors.find_available_position_in(-10..=20), Some(Position { x: -10, y: -10 }));
+	}
 }
@@ -4,37 +4,39 @@ mod day_01;
 mod day_02;
 mod day_03;
 mod day_04;
-mod day_05;
+pub mod day_05;
 mod day_06;
-mod day_07;
+pub mod day_07;
 mod day_08;
 mod day_09;
-mod day_10;
+pub mod day_10;
 mod day_11;
-mod day_12;
+pub mod day_12;
 mod day_13;
-mod day_14;
+pub mod day_14;
 mod day_15;
 mod day_16;
 mod day_18;
 
+use crate::Cli;
+
 /// Run specific AoC day.
-pub fn run(day: u8) {
-	match day {
+pub fn run(cli: &Cli) {
+	match cli.day {
 		1 => day_01::run(),
 		2 => day_02::run(),
 		3 => day_03::run(),
 		4 => day_04::run(),
-		5 => day_05::run(),
+		5 => day_05::run(cli.animate),
 		6 => day_06::run(),
 		7 => day_07::run(),
 		8 => day_08::run(),
-		9 => day_09::run(),
+		9 => day_09::run(cli.knots.map(usize::from), cli.animate),
 		10 => day_10::run(),
 		11 => day_11::run(),
 		12 => day_12::run(),
 		13 => day_13::run(),
-		14 => day_14::run(),
+		14 => day_14::run(cli.animate, usize::from(cli.frame_interval)),
 		15 => day_15::run(),
 		16 => day_16::run(),
 		18 => day_18::run(),
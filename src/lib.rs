@@ -1,6 +1,6 @@
 //! Functionality for AoC22.
 
-mod days;
+pub mod days;
 
 use clap::Parser;
 
@@ -10,12 +10,22 @@ pub struct Cli {
 	/// Advent of Code day.
 	#[arg(value_parser = clap::value_parser!(u8).range(1..=25))]
 	day: u8,
+	/// Print the intermediate states of the simulation (supported on days 5, 9
+	/// and 14).
+	#[arg(long)]
+	animate: bool,
+	/// Number of grains of sand between animation frames (used on day 14).
+	#[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+	frame_interval: u16,
+	/// Number of knots of an additional rope to simulate (supported on day 9).
+	#[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+	knots: Option<u16>,
 }
 
 impl Cli {
 	/// Run the advent of code day.
 	pub fn run(self) {
-		days::run(self.day);
+		days::run(&self);
 	}
 }
 